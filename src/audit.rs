@@ -0,0 +1,244 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::graph::{Node, Record};
+use crate::index::key;
+use crate::Databases;
+
+// Values that look like they were exported in place of missing data. Those
+// given with --null-value are already empty by the time the audit runs, so
+// they are reported from the load counts instead.
+const PLACEHOLDERS: &[&str] = &["None.", "None", "N/A", "NA", "Null", "-"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// One record that failed a check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding<'a> {
+    #[serde(rename = "Record")]
+    pub record: Record<'a>,
+    #[serde(rename = "Detail")]
+    pub detail: String,
+}
+
+// The records that failed one kind of check.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check<'a> {
+    #[serde(rename = "Check")]
+    pub name: &'static str,
+    #[serde(rename = "Severity")]
+    pub severity: Severity,
+    #[serde(rename = "Findings")]
+    pub findings: Vec<Finding<'a>>,
+    // Null values cleared while loading; only the placeholder check has these
+    #[serde(rename = "Cleared", skip_serializing_if = "Vec::is_empty")]
+    pub cleared: Vec<Cleared>,
+}
+
+impl Check<'_> {
+    fn new(name: &'static str, severity: Severity) -> Self {
+        Check {
+            name,
+            severity,
+            findings: Vec::new(),
+            cleared: Vec::new(),
+        }
+    }
+}
+
+// How many values of one field --null-value emptied while loading.
+#[derive(Debug, Clone, Serialize)]
+pub struct Cleared {
+    #[serde(rename = "Database")]
+    pub database: &'static str,
+    #[serde(rename = "Field")]
+    pub field: &'static str,
+    #[serde(rename = "Count")]
+    pub count: usize,
+}
+
+// Runs every consistency check over the databases. Checks that find nothing
+// are included, so the report always lists the same categories. `cleared`
+// counts the null values emptied while loading.
+pub fn audit<'a>(dbs: &'a Databases, cleared: &[Cleared]) -> Vec<Check<'a>> {
+    let assemblies = &dbs.assemblies.asm;
+    let drives = &dbs.drives.drive;
+    let tickets = &dbs.zendesk.zendesk_ticket;
+    let finding = |node: Node, detail: String| Finding {
+        record: dbs.record(node),
+        detail,
+    };
+
+    let mut duplicate_serials = Check::new("Duplicate assembly serial numbers", Severity::Error);
+    let mut by_serial: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, assembly) in assemblies.iter().enumerate() {
+        if !assembly.serial_number.trim().is_empty() {
+            by_serial
+                .entry(key(&assembly.serial_number))
+                .or_default()
+                .push(i);
+        }
+    }
+    for (i, assembly) in assemblies.iter().enumerate() {
+        let count = by_serial
+            .get(&key(&assembly.serial_number))
+            .map_or(0, Vec::len);
+        if count > 1 {
+            duplicate_serials.findings.push(finding(
+                Node::Assembly(i),
+                format!(
+                    "{} assemblies have serial number {}",
+                    count, assembly.serial_number
+                ),
+            ));
+        }
+    }
+
+    let mut duplicate_rmas = Check::new("Duplicate RMA numbers", Severity::Error);
+    let mut by_rma: HashMap<i64, usize> = HashMap::new();
+    for ticket in tickets {
+        *by_rma.entry(ticket.rma).or_default() += 1;
+    }
+    for (i, ticket) in tickets.iter().enumerate() {
+        let count = by_rma[&ticket.rma];
+        if count > 1 {
+            duplicate_rmas.findings.push(finding(
+                Node::Ticket(i),
+                format!(
+                    "{} tickets have RMA {}; only the first is shown in search results",
+                    count, ticket.rma
+                ),
+            ));
+        }
+    }
+
+    let mut orphan_drives = Check::new("Drives without an assembly", Severity::Error);
+    for (i, drive) in drives.iter().enumerate() {
+        if dbs.graph.parents(Node::Drive(i)).is_empty() {
+            orphan_drives.findings.push(finding(
+                Node::Drive(i),
+                format!("No assembly has serial number {:?}", drive.enclosure_sn),
+            ));
+        }
+    }
+
+    let mut orphan_tickets = Check::new("Tickets pointing nowhere", Severity::Error);
+    for (i, ticket) in tickets.iter().enumerate() {
+        let parents = dbs.graph.parents(Node::Ticket(i));
+        if is_present(&ticket.serial) && !parents.iter().any(|p| matches!(p, Node::Assembly(_))) {
+            orphan_tickets.findings.push(finding(
+                Node::Ticket(i),
+                format!("No assembly has serial number {}", ticket.serial),
+            ));
+        }
+        if is_present(&ticket.drive) && !parents.iter().any(|p| matches!(p, Node::Drive(_))) {
+            orphan_tickets.findings.push(finding(
+                Node::Ticket(i),
+                format!("No drive has serial number {}", ticket.drive),
+            ));
+        }
+    }
+
+    let mut assemblers = Check::new("Assembler differs from Built by", Severity::Warning);
+    for (i, ticket) in tickets.iter().enumerate() {
+        for parent in dbs.graph.parents(Node::Ticket(i)) {
+            if let Node::Assembly(a) = parent {
+                let assembly = &assemblies[*a];
+                if ticket.assembler_mismatch(assembly) {
+                    assemblers.findings.push(finding(
+                        Node::Ticket(i),
+                        format!(
+                            "Assembler is {} but assembly {} was built by {}",
+                            ticket.assembler, assembly.serial_number, assembly.built_by
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut zero_dates = Check::new("Assemblies without a build date", Severity::Warning);
+    for (i, assembly) in assemblies.iter().enumerate() {
+        if assembly.built_date == 0 {
+            zero_dates
+                .findings
+                .push(finding(Node::Assembly(i), "BuiltDate is 0".to_string()));
+        }
+    }
+
+    let mut placeholders = Check::new("Placeholder values", Severity::Warning);
+    placeholders.cleared = cleared.to_vec();
+    let mut check_fields = |node: Node, fields: &[(&str, &str)]| {
+        for (name, value) in fields {
+            if is_placeholder(value) {
+                placeholders.findings.push(finding(
+                    node,
+                    format!(
+                        "{} is {:?}; add it to --null-value to treat it as missing",
+                        name, value
+                    ),
+                ));
+            }
+        }
+    };
+    for (i, a) in assemblies.iter().enumerate() {
+        check_fields(
+            Node::Assembly(i),
+            &[
+                ("SerialNumber", &a.serial_number),
+                ("BuiltBy", &a.built_by),
+                ("Description", &a.description),
+                ("SalesOrder", &a.sales_order),
+            ],
+        );
+    }
+    for (i, d) in drives.iter().enumerate() {
+        check_fields(
+            Node::Drive(i),
+            &[
+                ("Enclosure SN", &d.enclosure_sn),
+                ("Drive SN", &d.drive_sn),
+                ("Drive Manufacturer", &d.drive_manufacturer),
+                ("Model", &d.model),
+                ("Part Number", &d.part_number),
+            ],
+        );
+    }
+    for (i, t) in tickets.iter().enumerate() {
+        check_fields(
+            Node::Ticket(i),
+            &[
+                ("Serial", &t.serial),
+                ("Drive", &t.drive),
+                ("Assembler", &t.assembler),
+                ("OldDiagnosis", &t.old_diagnosis),
+                ("NewDiagnosis", &t.new_diagnosis),
+            ],
+        );
+    }
+
+    vec![
+        duplicate_serials,
+        duplicate_rmas,
+        orphan_drives,
+        orphan_tickets,
+        assemblers,
+        zero_dates,
+        placeholders,
+    ]
+}
+
+fn is_placeholder(value: &str) -> bool {
+    PLACEHOLDERS
+        .iter()
+        .any(|placeholder| placeholder.eq_ignore_ascii_case(value.trim()))
+}
+
+// A reference that is filled in, as opposed to blank or a placeholder.
+fn is_present(value: &str) -> bool {
+    !value.trim().is_empty() && !is_placeholder(value)
+}
@@ -0,0 +1,213 @@
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use crate::dates::DateFormat;
+use crate::warranty::Term;
+
+// Command line arguments
+#[derive(Debug, Clone, Parser)]
+#[command(name = "glycli", version, about = "Glyph assemblies, drives and Zendesk data query")]
+pub struct Cli {
+    /// Directory containing ASM.json, DWE.json and ZEN.json
+    #[arg(long, global = true, env = "GLYCLI_DATA_DIR", default_value = ".")]
+    pub data_dir: PathBuf,
+
+    /// Path to the assemblies database (defaults to <data-dir>/ASM.json)
+    #[arg(long, global = true, env = "GLYCLI_ASSEMBLIES")]
+    pub assemblies: Option<PathBuf>,
+
+    /// Path to the drives with enclosures database (defaults to <data-dir>/DWE.json)
+    #[arg(long, global = true, env = "GLYCLI_DRIVES")]
+    pub drives: Option<PathBuf>,
+
+    /// Path to the Zendesk database (defaults to <data-dir>/ZEN.json)
+    #[arg(long, global = true, env = "GLYCLI_ZENDESK")]
+    pub zendesk: Option<PathBuf>,
+
+    /// Warranty rules file mapping descriptions and sales orders to terms
+    /// (defaults to <data-dir>/warranty.json; without one every unit gets 3 years)
+    #[arg(long, global = true, env = "GLYCLI_WARRANTY_RULES")]
+    pub warranty_rules: Option<PathBuf>,
+
+    /// Evaluate warranties at this date (YYYY-MM-DD) instead of today
+    #[arg(long, global = true, env = "GLYCLI_AS_OF", value_parser = parse_date)]
+    pub as_of: Option<NaiveDate>,
+
+    /// Skip malformed records instead of refusing to start
+    #[arg(
+        long,
+        global = true,
+        env = "GLYCLI_LENIENT",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    pub lenient: bool,
+
+    /// Values that stand for missing data, such as None.; fields holding one are
+    /// treated as empty, so they aren't shown, searched or used to link records
+    #[arg(
+        long = "null-value",
+        global = true,
+        env = "GLYCLI_NULL_VALUES",
+        value_delimiter = ',',
+        default_value = "None."
+    )]
+    pub null_values: Vec<String>,
+
+    /// Number of results per page; larger result sets are paged
+    #[arg(
+        long,
+        global = true,
+        env = "GLYCLI_PAGE_SIZE",
+        default_value_t = 25,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub page_size: usize,
+
+    /// File for the interactive prompt's history (defaults to ~/.glycli_history)
+    #[arg(long, global = true, env = "GLYCLI_HISTORY")]
+    pub history_file: Option<PathBuf>,
+
+    /// Output format for search results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Search once and exit: 0 found, 1 not found, 3 more results than shown
+    Query {
+        /// Number of results to skip
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Number of results to print, 0 for all; defaults to the page size
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Search term; read one term per line from stdin when omitted
+        term: Vec<String>,
+    },
+    /// Export search results, or a whole database when no term is given, as CSV or TSV
+    Export {
+        /// Which records to export
+        #[arg(value_enum)]
+        table: ExportTable,
+
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+
+        /// File to write; defaults to stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Search term; exports everything when omitted
+        term: Vec<String>,
+    },
+    /// Full-screen interface with a search box and result panes
+    Tui,
+    /// Check the databases for duplicates, broken links and placeholder values;
+    /// exits with 6 when errors are found
+    Audit {
+        /// Example records to show per check
+        #[arg(long, default_value_t = 5)]
+        examples: usize,
+    },
+    /// List assemblies whose warranty ends within a window either side of today
+    /// (or --as-of), grouped by sales order and description
+    WarrantyReport {
+        /// Size of the window on each side, e.g. 90d, 12w, 6m or 1y
+        #[arg(long, default_value = "90d", value_parser = parse_term)]
+        within: Term,
+
+        /// Write the assemblies as CSV or TSV instead of the report
+        #[arg(long, value_enum)]
+        format: Option<ExportFormat>,
+
+        /// File to write; defaults to stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Convert a date between epoch seconds and milliseconds, Excel serial
+    /// numbers, YYYY-MM-DD and MM-DD-YYYY, detecting the format of the input
+    Date {
+        /// Date to convert, e.g. 43186, 1522108800000, 2018-03-27 or 03-27-2018
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+
+        /// Read the value in this format instead of guessing
+        #[arg(long, value_enum)]
+        from: Option<DateFormat>,
+    },
+    /// Show how two records are linked, e.g. an assembly to a ticket
+    Path {
+        /// Serial number of an assembly or drive, or a ticket's RMA
+        from: String,
+
+        /// Serial number of an assembly or drive, or a ticket's RMA
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportTable {
+    Assemblies,
+    Drives,
+    Tickets,
+    /// One row per assembly-drive-ticket combination
+    Joined,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+}
+
+impl Cli {
+    pub fn assemblies_path(&self) -> PathBuf {
+        self.resolve(&self.assemblies, "ASM.json")
+    }
+
+    pub fn drives_path(&self) -> PathBuf {
+        self.resolve(&self.drives, "DWE.json")
+    }
+
+    pub fn zendesk_path(&self) -> PathBuf {
+        self.resolve(&self.zendesk, "ZEN.json")
+    }
+
+    pub fn warranty_rules_path(&self) -> PathBuf {
+        self.resolve(&self.warranty_rules, "warranty.json")
+    }
+
+    // The date warranties are evaluated at.
+    pub fn as_of(&self) -> NaiveDate {
+        self.as_of
+            .unwrap_or_else(|| chrono::Utc::now().naive_utc().date())
+    }
+
+    fn resolve(&self, explicit: &Option<PathBuf>, default_name: &str) -> PathBuf {
+        explicit
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join(default_name))
+    }
+}
+
+fn parse_date(text: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("invalid date `{}`; expected YYYY-MM-DD", text))
+}
+
+fn parse_term(text: &str) -> Result<Term, String> {
+    Term::try_from(text.to_string())
+}
@@ -0,0 +1,504 @@
+use chrono::{Duration, Months, NaiveDate};
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::warranty::Term;
+
+// Converts epoch milliseconds, as stored in BuiltDate, to a calendar date, or
+// None for dates chrono can't represent.
+pub fn epoch_millis_to_date(millis: i64) -> Option<NaiveDate> {
+    unix_epoch().checked_add_signed(Duration::try_days(millis.div_euclid(86400000))?)
+}
+
+// An inclusive range of dates; a missing end is unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+
+    // Parses a date filter relative to `today`:
+    //   2017, 2017-10, 2017-10-06   that year, month or day
+    //   <, <=, >, >= or = before any of the above
+    //   2016-11-01..2016-12-31      inclusive range; either side may be left out
+    //   <3y, >=90d                  compared with today minus N years, months,
+    //                               weeks or days, so <3y is older than 3 years
+    pub fn parse(text: &str, today: NaiveDate) -> Result<DateRange, String> {
+        let text = text.trim();
+
+        if let Some((start, end)) = text.split_once("..") {
+            let from = match start {
+                "" => None,
+                _ => Some(parse_period(start).ok_or_else(|| invalid(start))?.0),
+            };
+            let to = match end {
+                "" => None,
+                _ => Some(parse_period(end).ok_or_else(|| invalid(end))?.1),
+            };
+            if from.is_none() && to.is_none() {
+                return Err("A date range needs a start or an end".to_string());
+            }
+            return Ok(DateRange { from, to });
+        }
+
+        let (operator, value) = split_operator(text);
+        let (first, last) = match parse_relative(value, today) {
+            Some(date) => {
+                if operator.is_empty() {
+                    return Err(format!(
+                        "Relative date `{}` needs a comparison, e.g. <{} or >={}",
+                        value, value, value
+                    ));
+                }
+                (date, date)
+            }
+            None => parse_period(value).ok_or_else(|| invalid(value))?,
+        };
+
+        let range = match operator {
+            "<" => DateRange {
+                from: None,
+                to: first.pred_opt(),
+            },
+            "<=" => DateRange {
+                from: None,
+                to: Some(last),
+            },
+            ">" => DateRange {
+                from: last.succ_opt(),
+                to: None,
+            },
+            ">=" => DateRange {
+                from: Some(first),
+                to: None,
+            },
+            _ => DateRange {
+                from: Some(first),
+                to: Some(last),
+            },
+        };
+        Ok(range)
+    }
+}
+
+fn invalid(text: &str) -> String {
+    format!(
+        "Invalid date `{}`; expected YYYY, YYYY-MM, YYYY-MM-DD or an age like 3y",
+        text
+    )
+}
+
+fn split_operator(text: &str) -> (&str, &str) {
+    for operator in ["<=", ">=", "<", ">", "="] {
+        if let Some(rest) = text.strip_prefix(operator) {
+            return (operator, rest.trim());
+        }
+    }
+    ("", text)
+}
+
+// First and last day of a year, month or single day.
+pub fn parse_period(text: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let parts: Vec<&str> = text.split('-').collect();
+    let numbers: Vec<u32> = parts
+        .iter()
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        [year] if parts[0].len() == 4 => {
+            let first = NaiveDate::from_ymd_opt(*year as i32, 1, 1)?;
+            let last = NaiveDate::from_ymd_opt(*year as i32, 12, 31)?;
+            Some((first, last))
+        }
+        [year, month] if parts[0].len() == 4 => {
+            let first = NaiveDate::from_ymd_opt(*year as i32, *month, 1)?;
+            let last = first.checked_add_months(Months::new(1))?.pred_opt()?;
+            Some((first, last))
+        }
+        [year, month, day] if parts[0].len() == 4 => {
+            let date = NaiveDate::from_ymd_opt(*year as i32, *month, *day)?;
+            Some((date, date))
+        }
+        _ => None,
+    }
+}
+
+// `today` minus an age such as 3y, 6m, 2w or 90d.
+fn parse_relative(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    Term::parse(text)?.before(today)
+}
+
+// Ways of writing a date that the date converter reads and writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DateFormat {
+    /// Seconds since 1970-01-01 UTC
+    EpochSeconds,
+    /// Milliseconds since 1970-01-01 UTC, as in BuiltDate
+    EpochMillis,
+    /// Excel serial number in the 1900 date system (Windows Excel)
+    Excel1900,
+    /// Excel serial number in the 1904 date system (older Mac Excel)
+    Excel1904,
+    /// YYYY-MM-DD, optionally followed by a time
+    Iso,
+    /// MM-DD-YYYY or MM/DD/YYYY
+    Us,
+}
+
+impl DateFormat {
+    pub const ALL: &'static [DateFormat] = &[
+        DateFormat::Iso,
+        DateFormat::Us,
+        DateFormat::EpochSeconds,
+        DateFormat::EpochMillis,
+        DateFormat::Excel1900,
+        DateFormat::Excel1904,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DateFormat::EpochSeconds => "Epoch seconds",
+            DateFormat::EpochMillis => "Epoch milliseconds",
+            DateFormat::Excel1900 => "Excel serial (1900)",
+            DateFormat::Excel1904 => "Excel serial (1904)",
+            DateFormat::Iso => "ISO 8601",
+            DateFormat::Us => "US",
+        }
+    }
+
+    // Reads `text` in this format. Times of day are dropped.
+    pub fn read(self, text: &str) -> Result<NaiveDate, String> {
+        let text = text.trim();
+        let invalid = || format!("`{}` is not a valid {} date", text, self.name());
+        match self {
+            DateFormat::EpochSeconds => parse_number(text)
+                .and_then(|seconds| seconds.checked_mul(1000))
+                .and_then(epoch_millis_to_date)
+                .ok_or_else(invalid),
+            DateFormat::EpochMillis => parse_number(text)
+                .and_then(epoch_millis_to_date)
+                .ok_or_else(invalid),
+            DateFormat::Excel1900 => excel_1900_to_date(parse_number(text).ok_or_else(invalid)?),
+            DateFormat::Excel1904 => {
+                let serial = parse_number(text).ok_or_else(invalid)?;
+                if serial < 0 {
+                    return Err(format!(
+                        "Excel 1904 serial numbers start at 0 (1904-01-01), not {}",
+                        serial
+                    ));
+                }
+                Duration::try_days(serial)
+                    .and_then(|days| excel_1904_epoch().checked_add_signed(days))
+                    .ok_or_else(invalid)
+            }
+            DateFormat::Iso => {
+                let date = text.split(['T', 't', ' ']).next().unwrap_or_default();
+                NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())
+            }
+            DateFormat::Us => NaiveDate::parse_from_str(text, "%m-%d-%Y")
+                .or_else(|_| NaiveDate::parse_from_str(text, "%m/%d/%Y"))
+                .map_err(|_| invalid()),
+        }
+    }
+
+    // Writes `date` in this format, or None when the format can't express it,
+    // e.g. Excel serials before 1900.
+    pub fn write(self, date: NaiveDate) -> Option<String> {
+        let epoch_days = date.signed_duration_since(unix_epoch()).num_days();
+        match self {
+            DateFormat::EpochSeconds => Some((epoch_days * 86400).to_string()),
+            DateFormat::EpochMillis => Some((epoch_days * 86400000).to_string()),
+            DateFormat::Excel1900 => date_to_excel_1900(date).map(|serial| serial.to_string()),
+            DateFormat::Excel1904 => {
+                let serial = date.signed_duration_since(excel_1904_epoch()).num_days();
+                (serial >= 0).then(|| serial.to_string())
+            }
+            DateFormat::Iso => Some(date.format("%Y-%m-%d").to_string()),
+            DateFormat::Us => Some(date.format("%m-%d-%Y").to_string()),
+        }
+    }
+
+    // Guesses the format of `text`: dates with dashes or slashes by the
+    // position of the year, numbers by size. Numbers below 100,000 are Excel
+    // serials (up to the year 2173), below 100,000,000,000 epoch seconds (up to
+    // the year 5138) and anything larger epoch milliseconds.
+    pub fn detect(text: &str) -> Option<DateFormat> {
+        let text = text.trim();
+        if let Some(number) = parse_number(text) {
+            return Some(match number.unsigned_abs() {
+                0..100_000 if number > 0 => DateFormat::Excel1900,
+                0..100_000_000_000 => DateFormat::EpochSeconds,
+                _ => DateFormat::EpochMillis,
+            });
+        }
+        let first = text.split(['-', '/']).next()?;
+        if !first.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        match first.len() {
+            4 => Some(DateFormat::Iso),
+            1 | 2 => Some(DateFormat::Us),
+            _ => None,
+        }
+    }
+}
+
+// A whole number, allowing a fractional part (an Excel time of day), which is
+// rounded down.
+fn parse_number(text: &str) -> Option<i64> {
+    if let Ok(number) = text.parse() {
+        return Some(number);
+    }
+    // Plain decimals only, not 1e5, inf or NaN
+    if !text.contains('.')
+        || !text
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == '-')
+    {
+        return None;
+    }
+    let number: f64 = text.parse().ok()?;
+    (number.abs() < 1e15).then(|| number.floor() as i64)
+}
+
+fn unix_epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+}
+
+fn excel_1904_epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1904, 1, 1).unwrap()
+}
+
+// Excel's 1900 date system counts 1900-01-01 as day 1 but, copying Lotus 1-2-3,
+// treats 1900 as a leap year: day 60 is the nonexistent 1900-02-29, so from
+// day 61 (1900-03-01) on serials are one higher than a plain day count.
+pub fn excel_1900_to_date(serial: i64) -> Result<NaiveDate, String> {
+    let day_zero = NaiveDate::from_ymd_opt(1899, 12, 31).unwrap();
+    match serial {
+        ..=0 => Err(format!(
+            "Excel 1900 serial numbers start at 1 (1900-01-01), not {}",
+            serial
+        )),
+        60 => Err(
+            "Excel serial 60 is 1900-02-29, which doesn't exist; Excel treats 1900 as a leap year"
+                .to_string(),
+        ),
+        1..60 => Ok(day_zero + Duration::days(serial)),
+        _ => Duration::try_days(serial - 1)
+            .and_then(|days| day_zero.checked_add_signed(days))
+            .ok_or_else(|| format!("Excel serial {} is out of range", serial)),
+    }
+}
+
+pub fn date_to_excel_1900(date: NaiveDate) -> Option<i64> {
+    let day_zero = NaiveDate::from_ymd_opt(1899, 12, 31).unwrap();
+    let days = date.signed_duration_since(day_zero).num_days();
+    match days {
+        ..=0 => None,
+        1..60 => Some(days),
+        _ => Some(days + 1),
+    }
+}
+
+// A date read from one format, for writing in all of them.
+pub struct Conversion {
+    pub read_as: DateFormat,
+    pub date: NaiveDate,
+    // The same input read as an Excel 1904 serial, when it was taken for a
+    // 1900 one
+    pub alternative: Option<(DateFormat, NaiveDate)>,
+}
+
+impl Conversion {
+    // Reads `text` in format `from`, or in the detected format when None.
+    pub fn read(text: &str, from: Option<DateFormat>) -> Result<Conversion, String> {
+        let read_as = match from {
+            Some(format) => format,
+            None => DateFormat::detect(text).ok_or_else(|| {
+                format!(
+                    "Unrecognized date `{}`; expected a number, YYYY-MM-DD or MM-DD-YYYY",
+                    text.trim()
+                )
+            })?,
+        };
+        let date = read_as.read(text)?;
+        let alternative = match (from, read_as) {
+            (None, DateFormat::Excel1900) => DateFormat::Excel1904
+                .read(text)
+                .ok()
+                .map(|date| (DateFormat::Excel1904, date)),
+            _ => None,
+        };
+        Ok(Conversion {
+            read_as,
+            date,
+            alternative,
+        })
+    }
+
+    // The date in every format, None where a format can't express it.
+    pub fn representations(&self) -> Vec<(DateFormat, Option<String>)> {
+        DateFormat::ALL
+            .iter()
+            .map(|format| (*format, format.write(self.date)))
+            .collect()
+    }
+}
+
+// A date written as YYYY-MM-DD, MM-DD-YYYY or MM/DD/YYYY, not run into other
+// digits.
+static DATE_IN_TEXT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\D)(\d{4}-\d{1,2}-\d{1,2}|\d{1,2}[-/]\d{1,2}[-/]\d{4})(?:\D|$)").unwrap()
+});
+
+// The first valid date written somewhere in free text, such as a diagnosis.
+pub fn find_date(text: &str) -> Option<NaiveDate> {
+    DATE_IN_TEXT.captures_iter(text).find_map(|captures| {
+        let found = captures.get(1)?.as_str();
+        DateFormat::detect(found)?.read(found).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn epoch_millis_out_of_range_are_none() {
+        assert_eq!(epoch_millis_to_date(1507248000000), Some(date(2017, 10, 6)));
+        assert_eq!(epoch_millis_to_date(-1), Some(date(1969, 12, 31)));
+        assert_eq!(epoch_millis_to_date(9000000000000000000), None);
+        assert_eq!(epoch_millis_to_date(i64::MIN), None);
+    }
+
+    #[test]
+    fn excel_1900_skips_the_fake_leap_day() {
+        assert_eq!(excel_1900_to_date(1), Ok(date(1900, 1, 1)));
+        assert_eq!(excel_1900_to_date(59), Ok(date(1900, 2, 28)));
+        assert!(excel_1900_to_date(60).is_err());
+        assert_eq!(excel_1900_to_date(61), Ok(date(1900, 3, 1)));
+        assert_eq!(excel_1900_to_date(43186), Ok(date(2018, 3, 27)));
+        assert!(excel_1900_to_date(0).is_err());
+    }
+
+    #[test]
+    fn excel_1900_round_trips() {
+        assert_eq!(date_to_excel_1900(date(1900, 2, 28)), Some(59));
+        assert_eq!(date_to_excel_1900(date(1900, 3, 1)), Some(61));
+        assert_eq!(date_to_excel_1900(date(1899, 12, 31)), None);
+        for serial in [1, 59, 61, 43186] {
+            let converted = excel_1900_to_date(serial).unwrap();
+            assert_eq!(date_to_excel_1900(converted), Some(serial));
+        }
+    }
+
+    #[test]
+    fn excel_1904_counts_from_1904() {
+        assert_eq!(DateFormat::Excel1904.read("0"), Ok(date(1904, 1, 1)));
+        assert_eq!(DateFormat::Excel1904.read("41724"), Ok(date(2018, 3, 27)));
+        assert!(DateFormat::Excel1904.read("-1").is_err());
+        assert_eq!(
+            DateFormat::Excel1904.write(date(2018, 3, 27)),
+            Some("41724".to_string())
+        );
+        assert_eq!(DateFormat::Excel1904.write(date(1903, 12, 31)), None);
+    }
+
+    #[test]
+    fn detect_by_size_and_shape() {
+        assert_eq!(DateFormat::detect("43186"), Some(DateFormat::Excel1900));
+        assert_eq!(DateFormat::detect("43186.5"), Some(DateFormat::Excel1900));
+        assert_eq!(
+            DateFormat::detect("1522108800"),
+            Some(DateFormat::EpochSeconds)
+        );
+        assert_eq!(
+            DateFormat::detect("1522108800000"),
+            Some(DateFormat::EpochMillis)
+        );
+        assert_eq!(DateFormat::detect("0"), Some(DateFormat::EpochSeconds));
+        assert_eq!(DateFormat::detect("2018-03-27"), Some(DateFormat::Iso));
+        assert_eq!(DateFormat::detect("3/27/2018"), Some(DateFormat::Us));
+        assert_eq!(DateFormat::detect("nope"), None);
+        assert_eq!(DateFormat::detect("123-45"), None);
+    }
+
+    #[test]
+    fn read_each_format() {
+        let expected = Ok(date(2018, 3, 27));
+        assert_eq!(DateFormat::EpochSeconds.read("1522108800"), expected);
+        assert_eq!(DateFormat::EpochMillis.read("1522108800000"), expected);
+        assert_eq!(DateFormat::Excel1900.read("43186.75"), expected);
+        assert_eq!(DateFormat::Iso.read("2018-3-27T10:00:00"), expected);
+        assert_eq!(DateFormat::Us.read("03-27-2018"), expected);
+        assert_eq!(DateFormat::Us.read("3/27/2018"), expected);
+        assert!(DateFormat::Iso.read("2018-02-30").is_err());
+        assert!(DateFormat::EpochMillis.read("1e12").is_err());
+    }
+
+    #[test]
+    fn write_each_format() {
+        let day = date(2018, 3, 27);
+        let written: Vec<(DateFormat, Option<String>)> = DateFormat::ALL
+            .iter()
+            .map(|format| (*format, format.write(day)))
+            .collect();
+        assert_eq!(
+            written,
+            [
+                (DateFormat::Iso, Some("2018-03-27".to_string())),
+                (DateFormat::Us, Some("03-27-2018".to_string())),
+                (DateFormat::EpochSeconds, Some("1522108800".to_string())),
+                (DateFormat::EpochMillis, Some("1522108800000".to_string())),
+                (DateFormat::Excel1900, Some("43186".to_string())),
+                (DateFormat::Excel1904, Some("41724".to_string())),
+            ]
+        );
+        assert_eq!(DateFormat::Excel1900.write(date(1899, 12, 31)), None);
+    }
+
+    #[test]
+    fn conversion_offers_the_1904_reading_of_a_detected_serial() {
+        let conversion = Conversion::read("43186", None).unwrap();
+        assert_eq!(conversion.read_as, DateFormat::Excel1900);
+        assert_eq!(conversion.date, date(2018, 3, 27));
+        assert_eq!(
+            conversion.alternative,
+            Some((DateFormat::Excel1904, date(2022, 3, 28)))
+        );
+        let chosen = Conversion::read("43186", Some(DateFormat::Excel1900)).unwrap();
+        assert_eq!(chosen.alternative, None);
+    }
+
+    #[test]
+    fn find_date_in_diagnosis_text() {
+        assert_eq!(
+            find_date("Replaced on 03/27/2018."),
+            Some(date(2018, 3, 27))
+        );
+        assert_eq!(find_date("2018-3-7 fan swap"), Some(date(2018, 3, 7)));
+        assert_eq!(find_date("SN 12-34-567890"), None);
+        assert_eq!(find_date("13/45/2018"), None);
+    }
+
+    #[test]
+    fn relative_ranges_count_back_from_today() {
+        let today = date(2020, 5, 31);
+        let range = DateRange::parse("<3y", today).unwrap();
+        assert!(range.contains(date(2017, 5, 30)));
+        assert!(!range.contains(date(2017, 5, 31)));
+        assert!(DateRange::parse(">=2w", today)
+            .unwrap()
+            .contains(date(2020, 5, 17)));
+        assert!(DateRange::parse("3y", today).is_err());
+    }
+}
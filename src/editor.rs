@@ -0,0 +1,94 @@
+use rustyline::completion::{Completer, Pair};
+use rustyline::history::DefaultHistory;
+use rustyline::{Config, Context, Editor, Helper, Highlighter, Hinter, Validator};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::Databases;
+
+pub type SearchEditor = Editor<SearchHelper, DefaultHistory>;
+
+// Tab completion of serial numbers, sales orders, manufacturers and builder
+// names from the loaded databases.
+#[derive(Helper, Highlighter, Hinter, Validator)]
+pub struct SearchHelper {
+    words: Vec<String>,
+}
+
+impl SearchHelper {
+    fn new(dbs: &Databases) -> Self {
+        let mut words = BTreeSet::new();
+        for assembly in &dbs.assemblies.asm {
+            words.insert(assembly.serial_number.trim());
+            words.insert(assembly.sales_order.trim());
+            words.insert(assembly.built_by.trim());
+        }
+        for drive in &dbs.drives.drive {
+            words.insert(drive.drive_sn.trim());
+            words.insert(drive.drive_manufacturer.trim());
+        }
+        words.remove("");
+        SearchHelper {
+            words: words.into_iter().map(str::to_string).collect(),
+        }
+    }
+}
+
+impl Completer for SearchHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        // Inside an open quote the word runs from the quote; otherwise it
+        // starts after whitespace, a field name or a parenthesis.
+        let start = if before.matches('"').count() % 2 == 1 {
+            before.rfind('"').unwrap_or(0)
+        } else {
+            before
+                .rfind(|c: char| c.is_whitespace() || c == ':' || c == '(')
+                .map_or(0, |i| i + 1)
+        };
+        let prefix = before[start..].trim_start_matches('"').to_uppercase();
+        if prefix.is_empty() {
+            return Ok((start, Vec::new()));
+        }
+
+        let candidates = self
+            .words
+            .iter()
+            .filter(|word| word.to_uppercase().starts_with(&prefix))
+            .map(|word| Pair {
+                display: word.clone(),
+                replacement: if word.contains(char::is_whitespace) {
+                    format!("\"{}\"", word)
+                } else {
+                    word.clone()
+                },
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+// Line editor with history and completion for the interactive prompt.
+pub fn create_editor(dbs: &Databases) -> rustyline::Result<SearchEditor> {
+    let config = Config::builder()
+        .auto_add_history(true)
+        .max_history_size(1000)?
+        .build();
+    let mut editor = SearchEditor::with_config(config)?;
+    editor.set_helper(Some(SearchHelper::new(dbs)));
+    Ok(editor)
+}
+
+// ~/.glycli_history, when a home directory is known.
+pub fn default_history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".glycli_history"))
+}
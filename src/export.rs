@@ -0,0 +1,362 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::cli::{ExportFormat, ExportTable};
+use crate::graph::Node;
+use crate::search::SearchResults;
+use crate::warranty::{ExpiryGroup, WarrantyRules};
+use crate::{Assembly, Databases, Drive, ZendeskTicket};
+
+// A record that can be written as one spreadsheet row, using the column names
+// of the original JSON databases.
+trait Row {
+    const HEADERS: &'static [&'static str];
+
+    fn fields(&self) -> Vec<String>;
+}
+
+impl Row for Assembly {
+    const HEADERS: &'static [&'static str] = &[
+        "SerialNumber",
+        "BuiltDate",
+        "BuiltBy",
+        "Description",
+        "SalesOrder",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.serial_number.clone(),
+            self.built_date.to_string(),
+            self.built_by.clone(),
+            self.description.clone(),
+            self.sales_order.clone(),
+        ]
+    }
+}
+
+impl Row for Drive {
+    const HEADERS: &'static [&'static str] = &[
+        "Enclosure SN",
+        "Drive SN",
+        "Drive Manufacturer",
+        "Model",
+        "Part Number",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.enclosure_sn.clone(),
+            self.drive_sn.clone(),
+            self.drive_manufacturer.clone(),
+            self.model.clone(),
+            self.part_number.clone(),
+        ]
+    }
+}
+
+impl Row for ZendeskTicket {
+    const HEADERS: &'static [&'static str] = &[
+        "RMA",
+        "OpenedDate",
+        "Serial",
+        "Drive",
+        "Assembler",
+        "OldDiagnosis",
+        "NewDiagnosis",
+        "DiagnosisDate",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.rma.to_string(),
+            self.opened_date
+                .map(|opened| opened.to_string())
+                .unwrap_or_default(),
+            self.serial.clone(),
+            self.drive.clone(),
+            self.assembler.clone(),
+            self.old_diagnosis.clone(),
+            self.new_diagnosis.clone(),
+            self
+                .diagnosis_date
+                .map(|date| date.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+// Fields of an optional record, blank when it is absent.
+fn fields_or_blank<T: Row>(record: Option<&T>) -> Vec<String> {
+    match record {
+        Some(record) => record.fields(),
+        None => vec![String::new(); T::HEADERS.len()],
+    }
+}
+
+// Writes one table of the results to `out`, or to stdout when no file is given.
+pub fn export(
+    results: &SearchResults,
+    table: ExportTable,
+    format: ExportFormat,
+    out: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rows = match table {
+        ExportTable::Assemblies => results
+            .assemblies
+            .iter()
+            .map(|found| found.assembly.fields())
+            .collect(),
+        ExportTable::Drives => results_drives(results).map(Row::fields).collect(),
+        ExportTable::Tickets => results_tickets(results).map(Row::fields).collect(),
+        ExportTable::Joined => joined_rows(results),
+    };
+    write_rows(table, rows, format, out)
+}
+
+// Writes one table of a whole database, every record as loaded, including
+// records that repeat a serial number or RMA.
+pub fn export_all(
+    dbs: &Databases,
+    table: ExportTable,
+    format: ExportFormat,
+    out: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rows = match table {
+        ExportTable::Assemblies => dbs.assemblies.asm.iter().map(Row::fields).collect(),
+        ExportTable::Drives => dbs.drives.drive.iter().map(Row::fields).collect(),
+        ExportTable::Tickets => dbs.zendesk.zendesk_ticket.iter().map(Row::fields).collect(),
+        ExportTable::Joined => all_joined_rows(dbs),
+    };
+    write_rows(table, rows, format, out)
+}
+
+fn write_rows(
+    table: ExportTable,
+    rows: Vec<Vec<String>>,
+    format: ExportFormat,
+    out: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = writer(format, out)?;
+    let headers: Vec<&str> = match table {
+        ExportTable::Assemblies => Assembly::HEADERS.to_vec(),
+        ExportTable::Drives => Drive::HEADERS.to_vec(),
+        ExportTable::Tickets => ZendeskTicket::HEADERS.to_vec(),
+        ExportTable::Joined => Assembly::HEADERS
+            .iter()
+            .chain(Drive::HEADERS)
+            .chain(ZendeskTicket::HEADERS)
+            .copied()
+            .collect(),
+    };
+    writer.write_record(&headers)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Writes the assemblies of a warranty expiry report, one row each, with their
+// expiry date, days left and warranty rule.
+pub fn export_expiry_report(
+    groups: &[ExpiryGroup],
+    rules: &WarrantyRules,
+    format: ExportFormat,
+    out: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = writer(format, out)?;
+    let headers: Vec<&str> = Assembly::HEADERS
+        .iter()
+        .copied()
+        .chain(["Expires", "DaysLeft", "Rule"])
+        .collect();
+    writer.write_record(&headers)?;
+    for entry in groups.iter().flat_map(|group| &group.assemblies) {
+        let mut row = entry.assembly.fields();
+        row.extend([
+            entry.expires.clone(),
+            entry.days_left.to_string(),
+            entry.rule.to_string(),
+        ]);
+        writer.write_record(row)?;
+    }
+    // Unknown build dates leave Expires and DaysLeft blank
+    for group in groups {
+        for assembly in &group.unknown {
+            let mut row = assembly.fields();
+            let (rule, _) = rules.rule_for(assembly);
+            row.extend([String::new(), String::new(), rule.to_string()]);
+            writer.write_record(row)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// A CSV or TSV writer to `out`, or to stdout when no file is given.
+fn writer(format: ExportFormat, out: Option<&Path>) -> io::Result<csv::Writer<Box<dyn Write>>> {
+    let sink: Box<dyn Write> = match out {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let delimiter = match format {
+        ExportFormat::Csv => b',',
+        ExportFormat::Tsv => b'\t',
+    };
+    Ok(csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(sink))
+}
+
+fn results_drives<'a>(results: &'a SearchResults) -> impl Iterator<Item = &'a Drive> {
+    results
+        .assemblies
+        .iter()
+        .flat_map(|found| found.drives.iter().copied())
+        .chain(results.drives.iter().map(|found| found.drive))
+}
+
+fn results_tickets<'a>(results: &'a SearchResults) -> impl Iterator<Item = &'a ZendeskTicket> {
+    results
+        .assemblies
+        .iter()
+        .flat_map(|found| found.tickets.iter().copied())
+        .chain(
+            results
+                .drives
+                .iter()
+                .flat_map(|found| found.tickets.iter().copied()),
+        )
+        .chain(results.tickets.iter().flat_map(|found| {
+            std::iter::once(found.ticket).chain(found.tickets.iter().copied())
+        }))
+}
+
+// Each record as `Some`, or a single `None` when there are no records.
+fn some_or_none<'a, T>(records: &[&'a T]) -> Vec<Option<&'a T>> {
+    if records.is_empty() {
+        vec![None]
+    } else {
+        records.iter().copied().map(Some).collect()
+    }
+}
+
+// Joined rows for a whole database: each assembly with the drives and tickets
+// linked to it, then drives without an assembly with their tickets, then any
+// ticket not written yet with the assembly and drive it points to. Every
+// record appears in at least one row.
+fn all_joined_rows(dbs: &Databases) -> Vec<Vec<String>> {
+    let graph = &dbs.graph;
+    let drive = |node: &Node| match node {
+        Node::Drive(i) => Some(&dbs.drives.drive[*i]),
+        _ => None,
+    };
+    let assembly = |node: &Node| match node {
+        Node::Assembly(i) => Some(&dbs.assemblies.asm[*i]),
+        _ => None,
+    };
+    let mut written = vec![false; dbs.zendesk.zendesk_ticket.len()];
+    let mut tickets = |nodes: &[Node]| -> Vec<&ZendeskTicket> {
+        nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Ticket(i) => {
+                    written[*i] = true;
+                    Some(&dbs.zendesk.zendesk_ticket[*i])
+                }
+                _ => None,
+            })
+            .collect()
+    };
+    let mut rows = Vec::new();
+
+    for (i, found) in dbs.assemblies.asm.iter().enumerate() {
+        let children = graph.children(Node::Assembly(i));
+        let drives: Vec<&Drive> = children.iter().filter_map(drive).collect();
+        let found_tickets = tickets(children);
+        for drive in some_or_none(&drives) {
+            for ticket in some_or_none(&found_tickets) {
+                let mut row = found.fields();
+                row.extend(fields_or_blank(drive));
+                row.extend(fields_or_blank(ticket));
+                rows.push(row);
+            }
+        }
+    }
+
+    for (i, found) in dbs.drives.drive.iter().enumerate() {
+        if !graph.parents(Node::Drive(i)).is_empty() {
+            continue;
+        }
+        for ticket in some_or_none(&tickets(graph.children(Node::Drive(i)))) {
+            let mut row = fields_or_blank::<Assembly>(None);
+            row.extend(found.fields());
+            row.extend(fields_or_blank(ticket));
+            rows.push(row);
+        }
+    }
+
+    for (i, found) in dbs.zendesk.zendesk_ticket.iter().enumerate() {
+        if written[i] {
+            continue;
+        }
+        let parents = graph.parents(Node::Ticket(i));
+        let found_drive = parents.iter().find_map(drive);
+        // A ticket filed only against a drive is shown with the drive's enclosure
+        let found_assembly = parents.iter().find_map(assembly).or_else(|| {
+            parents
+                .iter()
+                .filter(|node| matches!(node, Node::Drive(_)))
+                .flat_map(|node| graph.parents(*node))
+                .find_map(assembly)
+        });
+        let mut row = fields_or_blank(found_assembly);
+        row.extend(fields_or_blank(found_drive));
+        row.extend(found.fields());
+        rows.push(row);
+    }
+
+    rows
+}
+
+// One row per assembly-drive-ticket combination. An assembly without drives or
+// tickets still gets a row, with those columns left blank.
+fn joined_rows(results: &SearchResults) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+
+    for found in &results.assemblies {
+        let drives = some_or_none(&found.drives);
+        let tickets = some_or_none(&found.tickets);
+        for drive in &drives {
+            for ticket in &tickets {
+                let mut row = found.assembly.fields();
+                row.extend(fields_or_blank(*drive));
+                row.extend(fields_or_blank(*ticket));
+                rows.push(row);
+            }
+        }
+    }
+
+    for found in &results.drives {
+        let tickets = some_or_none(&found.tickets);
+        for ticket in tickets {
+            let mut row = fields_or_blank(found.enclosure);
+            row.extend(found.drive.fields());
+            row.extend(fields_or_blank(ticket));
+            rows.push(row);
+        }
+    }
+
+    for found in &results.tickets {
+        for ticket in std::iter::once(found.ticket).chain(found.tickets.iter().copied()) {
+            let mut row = fields_or_blank(found.assembly);
+            row.extend(fields_or_blank(found.drive));
+            row.extend(ticket.fields());
+            rows.push(row);
+        }
+    }
+
+    rows
+}
@@ -0,0 +1,153 @@
+use crate::query::Query;
+use crate::Databases;
+
+// Cost of inserting, deleting, replacing or swapping a character. Swapping
+// characters that look alike on a label costs half as much.
+const EDIT: usize = 2;
+const CONFUSABLE: usize = 1;
+const CONFUSABLES: &[(char, char)] = &[('O', '0'), ('I', '1')];
+
+// Suggestions shown after a search finds nothing.
+const MAX_SUGGESTIONS: usize = 5;
+
+fn substitution_cost(a: char, b: char) -> usize {
+    if a == b {
+        0
+    } else if CONFUSABLES
+        .iter()
+        .any(|&(x, y)| (a, b) == (x, y) || (a, b) == (y, x))
+    {
+        CONFUSABLE
+    } else {
+        EDIT
+    }
+}
+
+// Largest distance at which a value still counts as a typo of `text`: one
+// edit for short text, two for serial-number length text.
+pub fn max_distance(text: &str) -> usize {
+    if text.chars().count() < 8 {
+        EDIT
+    } else {
+        2 * EDIT
+    }
+}
+
+// Edit distance between two uppercased strings, counting adjacent swaps as
+// one edit, or None if it is more than `max`.
+pub fn distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) * EDIT > max {
+        return None;
+    }
+
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).map(|j| j * EDIT).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i * EDIT; b.len() + 1];
+        for j in 1..=b.len() {
+            let mut cost = (previous[j - 1] + substitution_cost(a[i - 1], b[j - 1]))
+                .min(previous[j] + EDIT)
+                .min(current[j - 1] + EDIT);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cost = cost.min(before[j - 2] + EDIT);
+            }
+            current[j] = cost;
+        }
+        if current.iter().min().is_some_and(|&least| least > max) {
+            return None;
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    Some(previous[b.len()]).filter(|&d| d <= max)
+}
+
+// Whether an uppercased value is within typo distance of `text`.
+pub fn is_close(value: &str, text: &str) -> bool {
+    distance(value, text, max_distance(text)).is_some()
+}
+
+// Serial numbers close to the text terms of a search, closest first.
+pub fn suggestions(dbs: &Databases, input: &str) -> Vec<String> {
+    let Ok(query) = Query::parse(input) else {
+        return Vec::new();
+    };
+    let mut ranked: Vec<(usize, &String)> = Vec::new();
+    for text in query.serial_texts() {
+        let max = max_distance(&text);
+        for (key, serial) in &dbs.index.serials {
+            if let Some(d) = distance(key, &text, max) {
+                ranked.push((d, serial));
+            }
+        }
+    }
+    ranked.sort();
+    let mut suggestions: Vec<String> = Vec::new();
+    for (_, serial) in ranked {
+        if !suggestions.contains(serial) {
+            suggestions.push(serial.clone());
+        }
+        if suggestions.len() == MAX_SUGGESTIONS {
+            break;
+        }
+    }
+    suggestions
+}
+
+// "Did you mean ...?" for a search that found nothing, if anything is close.
+pub fn did_you_mean(dbs: &Databases, input: &str) -> Option<String> {
+    let suggestions = suggestions(dbs, input);
+    if suggestions.is_empty() {
+        return None;
+    }
+    Some(format!("Did you mean {}?", suggestions.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_strings_are_zero_apart() {
+        assert_eq!(distance("1BBK1234", "1BBK1234", 0), Some(0));
+        assert_eq!(distance("", "", 0), Some(0));
+    }
+
+    #[test]
+    fn each_edit_costs_two() {
+        // Replace, insert and delete
+        assert_eq!(distance("1BBK1234", "1BBK1235", 4), Some(2));
+        assert_eq!(distance("1BBK1234", "1BBK12345", 4), Some(2));
+        assert_eq!(distance("1BBK1234", "1BBK123", 4), Some(2));
+        assert_eq!(distance("1BBK1234", "2BBK1235", 4), Some(4));
+    }
+
+    #[test]
+    fn adjacent_swaps_are_one_edit() {
+        assert_eq!(distance("1BBK1234", "1BBK1243", 4), Some(2));
+        assert_eq!(distance("AB", "BA", 2), Some(2));
+    }
+
+    #[test]
+    fn look_alike_characters_cost_half() {
+        assert_eq!(distance("1BBKO0I1", "1BBK00I1", 4), Some(1));
+        assert_eq!(distance("1BBK0011", "1BBKOOII", 4), Some(4));
+    }
+
+    #[test]
+    fn distances_over_the_maximum_are_none() {
+        assert_eq!(distance("1BBK1234", "1BBK1299", 3), None);
+        // Caught by the length difference before any comparison
+        assert_eq!(distance("1BBK", "1BBK1234", 6), None);
+        assert_eq!(distance("ABCDEFGH", "STUVWXYZ", 4), None);
+    }
+
+    #[test]
+    fn longer_text_allows_more_typos() {
+        assert!(is_close("1BBK1234567890", "1BBK1243567899"));
+        assert!(!is_close("1BBK1234567890", "1BBK1243567999"));
+        assert!(is_close("ATOM", "ATON"));
+        assert!(!is_close("ATOM", "AXON"));
+    }
+}
@@ -0,0 +1,347 @@
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+use crate::index::key;
+use crate::{AssembliesRoot, Assembly, Drive, DrivesRoot, ZendeskRoot, ZendeskTicket};
+
+// A record in the graph, by its position in its database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Node {
+    Assembly(usize),
+    Drive(usize),
+    Ticket(usize),
+}
+
+// The record a node stands for.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum Record<'a> {
+    Assembly(&'a Assembly),
+    Drive(&'a Drive),
+    #[serde(rename = "ZendeskTicket")]
+    Ticket(&'a ZendeskTicket),
+}
+
+impl Record<'_> {
+    // The record's kind and serial number, or RMA for tickets.
+    pub fn label(&self) -> String {
+        match self {
+            Record::Assembly(assembly) => format!("Assembly {}", assembly.serial_number),
+            Record::Drive(drive) => format!("Drive {}", drive.drive_sn),
+            Record::Ticket(ticket) => format!("Zendesk Ticket RMA {}", ticket.rma),
+        }
+    }
+}
+
+// Links between assemblies, drives and tickets, built once at load time.
+// An assembly is the parent of the drives in its enclosure and of the tickets
+// filed against its serial number; a drive is the parent of the tickets filed
+// against its drive serial number.
+pub struct Graph {
+    // Assemblies and drives by serial number and tickets by RMA, uppercased
+    by_key: HashMap<String, Vec<Node>>,
+    assembly_children: Vec<Vec<Node>>,
+    drive_parents: Vec<Vec<Node>>,
+    drive_children: Vec<Vec<Node>>,
+    ticket_parents: Vec<Vec<Node>>,
+    // Drives grouped by enclosure serial number, whether or not there is an
+    // assembly with that serial number, and each drive's group
+    enclosures: Vec<Vec<usize>>,
+    drive_enclosure: Vec<usize>,
+}
+
+impl Graph {
+    pub fn build(assemblies: &AssembliesRoot, drives: &DrivesRoot, zendesk: &ZendeskRoot) -> Self {
+        let assemblies = &assemblies.asm;
+        let drives = &drives.drive;
+        let tickets = &zendesk.zendesk_ticket;
+
+        let assemblies_by_serial = by_serial(assemblies.iter().map(|a| a.serial_number.as_str()));
+        let drives_by_serial = by_serial(drives.iter().map(|d| d.drive_sn.as_str()));
+
+        let mut by_key: HashMap<String, Vec<Node>> = HashMap::new();
+        for (serial, found) in &assemblies_by_serial {
+            let nodes = by_key.entry(serial.clone()).or_default();
+            nodes.extend(found.iter().map(|&i| Node::Assembly(i)));
+        }
+        for (serial, found) in &drives_by_serial {
+            let nodes = by_key.entry(serial.clone()).or_default();
+            nodes.extend(found.iter().map(|&i| Node::Drive(i)));
+        }
+        for (i, ticket) in tickets.iter().enumerate() {
+            by_key
+                .entry(ticket.rma.to_string())
+                .or_default()
+                .push(Node::Ticket(i));
+        }
+
+        let mut assembly_children = vec![Vec::new(); assemblies.len()];
+        let mut drive_parents = vec![Vec::new(); drives.len()];
+        let mut drive_children = vec![Vec::new(); drives.len()];
+        let mut ticket_parents = vec![Vec::new(); tickets.len()];
+        let mut enclosure_groups: HashMap<String, usize> = HashMap::new();
+        let mut enclosures: Vec<Vec<usize>> = Vec::new();
+        let mut drive_enclosure = Vec::with_capacity(drives.len());
+
+        for (d, drive) in drives.iter().enumerate() {
+            let enclosure = key(&drive.enclosure_sn);
+            for &a in assemblies_by_serial.get(&enclosure).into_iter().flatten() {
+                assembly_children[a].push(Node::Drive(d));
+                drive_parents[d].push(Node::Assembly(a));
+            }
+            // Drives with no enclosure serial number aren't siblings
+            let group = if enclosure.trim().is_empty() {
+                enclosures.push(Vec::new());
+                enclosures.len() - 1
+            } else {
+                *enclosure_groups.entry(enclosure).or_insert_with(|| {
+                    enclosures.push(Vec::new());
+                    enclosures.len() - 1
+                })
+            };
+            enclosures[group].push(d);
+            drive_enclosure.push(group);
+        }
+        for (t, ticket) in tickets.iter().enumerate() {
+            for &a in assemblies_by_serial
+                .get(&key(&ticket.serial))
+                .into_iter()
+                .flatten()
+            {
+                assembly_children[a].push(Node::Ticket(t));
+                ticket_parents[t].push(Node::Assembly(a));
+            }
+            for &d in drives_by_serial
+                .get(&key(&ticket.drive))
+                .into_iter()
+                .flatten()
+            {
+                drive_children[d].push(Node::Ticket(t));
+                ticket_parents[t].push(Node::Drive(d));
+            }
+        }
+
+        Graph {
+            by_key,
+            assembly_children,
+            drive_parents,
+            drive_children,
+            ticket_parents,
+            enclosures,
+            drive_enclosure,
+        }
+    }
+
+    // Assemblies and drives with this serial number, or tickets with this RMA.
+    // Blank serial numbers match nothing.
+    pub fn find(&self, serial: &str) -> &[Node] {
+        self.by_key
+            .get(&key(serial.trim()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    // An assembly's drives and tickets, or a drive's tickets.
+    pub fn children(&self, node: Node) -> &[Node] {
+        match node {
+            Node::Assembly(i) => &self.assembly_children[i],
+            Node::Drive(i) => &self.drive_children[i],
+            Node::Ticket(_) => &[],
+        }
+    }
+
+    // The assembly a drive is installed in, or the assembly and drive a
+    // ticket was filed against.
+    pub fn parents(&self, node: Node) -> &[Node] {
+        match node {
+            Node::Assembly(_) => &[],
+            Node::Drive(i) => &self.drive_parents[i],
+            Node::Ticket(i) => &self.ticket_parents[i],
+        }
+    }
+
+    // The other drives in a drive's enclosure.
+    pub fn siblings(&self, node: Node) -> impl Iterator<Item = Node> + '_ {
+        let drives = match node {
+            Node::Drive(i) => self.enclosures[self.drive_enclosure[i]].as_slice(),
+            _ => &[],
+        };
+        drives
+            .iter()
+            .filter(move |&&d| Node::Drive(d) != node)
+            .map(|&d| Node::Drive(d))
+    }
+
+    // Every record linked directly to this one.
+    pub fn neighbors(&self, node: Node) -> impl Iterator<Item = Node> + '_ {
+        self.parents(node)
+            .iter()
+            .chain(self.children(node))
+            .copied()
+    }
+
+    // The shortest chain of links from a record with serial number (or RMA)
+    // `from` to one with `to`, including both ends.
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<Node>> {
+        let targets = self.find(to);
+        let mut previous: HashMap<Node, Option<Node>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for &start in self.find(from) {
+            previous.insert(start, None);
+            queue.push_back(start);
+        }
+
+        while let Some(node) = queue.pop_front() {
+            if targets.contains(&node) {
+                let mut path = vec![node];
+                while let Some(&Some(before)) = previous.get(path.last()?) {
+                    path.push(before);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.neighbors(node) {
+                if let Entry::Vacant(entry) = previous.entry(next) {
+                    entry.insert(Some(node));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+// Positions of the records with each serial number, by key. Blank serial
+// numbers are left out so that missing values never link records.
+fn by_serial<'a>(serials: impl Iterator<Item = &'a str>) -> HashMap<String, Vec<usize>> {
+    let mut map: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, serial) in serials.enumerate() {
+        if !serial.trim().is_empty() {
+            map.entry(key(serial)).or_default().push(i);
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Assemblies A1 and A2. D1 and D2 are in A1, D3 is in A2, D4 and D7 share
+    // an enclosure with no assembly and D5 and D6 have no enclosure. Ticket 100
+    // is for A1 and D1, ticket 200 only for D3 and ticket 300 for nothing.
+    fn graph() -> Graph {
+        let assemblies = AssembliesRoot {
+            asm: ["A1", "A2"]
+                .map(|serial| Assembly {
+                    serial_number: serial.to_string(),
+                    ..Assembly::default()
+                })
+                .to_vec(),
+        };
+        let drives = DrivesRoot {
+            drive: [
+                ("D1", "A1"),
+                ("D2", "a1"),
+                ("D3", "A2"),
+                ("D4", "X9"),
+                ("D5", ""),
+                ("D6", " "),
+                ("D7", "X9"),
+            ]
+            .map(|(serial, enclosure)| Drive {
+                drive_sn: serial.to_string(),
+                enclosure_sn: enclosure.to_string(),
+                ..Drive::default()
+            })
+            .to_vec(),
+        };
+        let zendesk = ZendeskRoot {
+            zendesk_ticket: [(100, "A1", "D1"), (200, "", "D3"), (300, "", "")]
+                .map(|(rma, serial, drive)| ZendeskTicket {
+                    rma,
+                    serial: serial.to_string(),
+                    drive: drive.to_string(),
+                    ..ZendeskTicket::default()
+                })
+                .to_vec(),
+        };
+        Graph::build(&assemblies, &drives, &zendesk)
+    }
+
+    #[test]
+    fn unknown_serials_find_nothing() {
+        let graph = graph();
+        assert!(graph.find("NOPE").is_empty());
+        assert!(graph.find("").is_empty());
+        assert!(graph.find("  ").is_empty());
+        assert_eq!(graph.path("NOPE", "A1"), None);
+        assert_eq!(graph.path("A1", "NOPE"), None);
+    }
+
+    #[test]
+    fn serials_are_found_ignoring_case_and_spaces() {
+        let graph = graph();
+        assert_eq!(graph.find(" a1 "), [Node::Assembly(0)]);
+        assert_eq!(graph.find("d3"), [Node::Drive(2)]);
+        assert_eq!(graph.find("100"), [Node::Ticket(0)]);
+    }
+
+    #[test]
+    fn parents_and_children_link_records() {
+        let graph = graph();
+        assert_eq!(
+            graph.children(Node::Assembly(0)),
+            [Node::Drive(0), Node::Drive(1), Node::Ticket(0)]
+        );
+        assert_eq!(graph.parents(Node::Drive(1)), [Node::Assembly(0)]);
+        assert_eq!(graph.children(Node::Drive(0)), [Node::Ticket(0)]);
+        assert_eq!(
+            graph.parents(Node::Ticket(0)),
+            [Node::Assembly(0), Node::Drive(0)]
+        );
+        assert_eq!(graph.parents(Node::Ticket(1)), [Node::Drive(2)]);
+    }
+
+    #[test]
+    fn records_without_a_parent() {
+        let graph = graph();
+        assert!(graph.parents(Node::Assembly(0)).is_empty());
+        // Enclosure with no assembly, and no enclosure at all
+        assert!(graph.parents(Node::Drive(3)).is_empty());
+        assert!(graph.parents(Node::Drive(4)).is_empty());
+        assert!(graph.parents(Node::Ticket(2)).is_empty());
+        assert!(graph.children(Node::Ticket(0)).is_empty());
+        assert_eq!(graph.neighbors(Node::Ticket(2)).count(), 0);
+    }
+
+    #[test]
+    fn siblings_share_an_enclosure() {
+        let graph = graph();
+        let siblings = |node| graph.siblings(node).collect::<Vec<_>>();
+        assert_eq!(siblings(Node::Drive(0)), [Node::Drive(1)]);
+        assert_eq!(siblings(Node::Drive(2)), []);
+        // Even when there is no assembly for the enclosure
+        assert_eq!(siblings(Node::Drive(3)), [Node::Drive(6)]);
+        // Blank enclosures don't make drives siblings
+        assert_eq!(siblings(Node::Drive(4)), []);
+        assert_eq!(siblings(Node::Drive(5)), []);
+        assert_eq!(siblings(Node::Assembly(0)), []);
+    }
+
+    #[test]
+    fn paths_follow_links_through_parents() {
+        let graph = graph();
+        assert_eq!(
+            graph.path("D1", "D2"),
+            Some(vec![Node::Drive(0), Node::Assembly(0), Node::Drive(1)])
+        );
+        assert_eq!(
+            graph.path("200", "A2"),
+            Some(vec![Node::Ticket(1), Node::Drive(2), Node::Assembly(1)])
+        );
+        assert_eq!(graph.path("A1", "a1"), Some(vec![Node::Assembly(0)]));
+        // Siblings with no assembly aren't linked
+        assert_eq!(graph.path("D4", "D7"), None);
+        assert_eq!(graph.path("D1", "D3"), None);
+    }
+}
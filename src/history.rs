@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+
+use crate::search::SearchResults;
+
+// Oldest result sets are forgotten beyond this many; numbers are never reused.
+const HISTORY_LIMIT: usize = 100;
+
+// A search made during this session and its results.
+pub struct HistoryEntry<'a> {
+    pub number: usize,
+    pub query: String,
+    pub results: SearchResults<'a>,
+}
+
+// Numbered result sets of the searches made in this session.
+#[derive(Default)]
+pub struct SessionHistory<'a> {
+    entries: VecDeque<HistoryEntry<'a>>,
+    next_number: usize,
+}
+
+impl<'a> SessionHistory<'a> {
+    // Stores a result set and returns its number, starting from 1.
+    pub fn push(&mut self, query: &str, results: SearchResults<'a>) -> usize {
+        self.next_number += 1;
+        if self.entries.len() == HISTORY_LIMIT {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry {
+            number: self.next_number,
+            query: query.to_string(),
+            results,
+        });
+        self.next_number
+    }
+
+    pub fn get(&self, number: usize) -> Option<&HistoryEntry<'a>> {
+        self.entries.iter().find(|entry| entry.number == number)
+    }
+
+    // The most recent `count` entries, oldest first.
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &HistoryEntry<'a>> {
+        self.entries.iter().skip(self.entries.len().saturating_sub(count))
+    }
+}
+
+// What a `#` command asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recall {
+    // `#`: list recent searches
+    List,
+    // `#3`: result set 3
+    Set(usize),
+    // `#3.2`: the second record of result set 3
+    Record(usize, usize),
+}
+
+impl Recall {
+    // Parses the text after `#`.
+    pub fn parse(text: &str) -> Result<Recall, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(Recall::List);
+        }
+        let invalid = || format!("Invalid recall `#{}`; use #, #N or #N.M", text);
+        let number = |part: &str| part.parse::<usize>().ok().filter(|n| *n > 0);
+        match text.split_once('.') {
+            Some((set, record)) => Ok(Recall::Record(
+                number(set).ok_or_else(invalid)?,
+                number(record).ok_or_else(invalid)?,
+            )),
+            None => Ok(Recall::Set(number(text).ok_or_else(invalid)?)),
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDate;
+
+use crate::query::{Field, Query, Searchable};
+use crate::{AssembliesRoot, DrivesRoot, ZendeskRoot};
+
+// Indexes built once at load time so that searches don't scan and uppercase
+// every record on each query.
+pub struct SearchIndex {
+    pub assemblies: TableIndex,
+    pub drives: TableIndex,
+    pub tickets: TableIndex,
+    // Every assembly, enclosure and drive serial number by its key, for
+    // suggestions
+    pub serials: BTreeMap<String, String>,
+}
+
+impl SearchIndex {
+    pub fn build(assemblies: &AssembliesRoot, drives: &DrivesRoot, zendesk: &ZendeskRoot) -> Self {
+        SearchIndex {
+            assemblies: TableIndex::build(&assemblies.asm),
+            drives: TableIndex::build(&drives.drive),
+            tickets: TableIndex::build(&zendesk.zendesk_ticket),
+            serials: assemblies
+                .asm
+                .iter()
+                .map(|a| a.serial_number.as_str())
+                .chain(
+                    drives
+                        .drive
+                        .iter()
+                        .flat_map(|d| [d.enclosure_sn.as_str(), d.drive_sn.as_str()]),
+                )
+                .map(str::trim)
+                .filter(|serial| !serial.is_empty())
+                .map(|serial| (key(serial), serial.to_string()))
+                .collect(),
+        }
+    }
+}
+
+// Normalized form of a value used as an index key.
+pub fn key(value: &str) -> String {
+    value.to_uppercase()
+}
+
+// Identifier fields, also indexed by whole value. They have far fewer
+// distinct values than records, so text too short for trigrams is looked up
+// among those values instead of scanning every record.
+const KEY_FIELDS: &[Field] = &[Field::Serial, Field::Drive, Field::Rma, Field::SalesOrder];
+
+// Uppercased field values of one kind of record, a trigram index over them
+// for substring search and, for identifier fields, the records holding each
+// whole value.
+#[derive(Default)]
+pub struct TableIndex {
+    values: HashMap<Field, Vec<Vec<String>>>,
+    trigrams: HashMap<(Field, [char; 3]), Vec<u32>>,
+    keys: HashMap<Field, HashMap<String, Vec<u32>>>,
+}
+
+impl TableIndex {
+    fn build<R: Searchable>(records: &[R]) -> Self {
+        let mut index = TableIndex::default();
+        for field in Field::ALL {
+            if records.is_empty() || records[0].field_values(*field).is_none() {
+                continue;
+            }
+            let mut rows = Vec::with_capacity(records.len());
+            for (id, record) in records.iter().enumerate() {
+                let values: Vec<String> = record
+                    .field_values(*field)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Cow::into_owned)
+                    .collect();
+                for value in &values {
+                    if KEY_FIELDS.contains(field) && !value.trim().is_empty() {
+                        let postings = index
+                            .keys
+                            .entry(*field)
+                            .or_default()
+                            .entry(value.trim().to_string())
+                            .or_default();
+                        if postings.last() != Some(&(id as u32)) {
+                            postings.push(id as u32);
+                        }
+                    }
+                    for trigram in trigrams(value) {
+                        let postings = index.trigrams.entry((*field, trigram)).or_default();
+                        if postings.last() != Some(&(id as u32)) {
+                            postings.push(id as u32);
+                        }
+                    }
+                }
+                rows.push(values);
+            }
+            index.values.insert(*field, rows);
+        }
+        index
+    }
+
+    // Positions of records that may contain `text` (uppercased) in one of
+    // `fields`, in order, or None when every record has to be checked.
+    pub fn lookup(&self, fields: &[Field], text: &str) -> Option<Vec<u32>> {
+        let grams: Vec<[char; 3]> = trigrams(text).collect();
+        if grams.is_empty() {
+            return self.lookup_short(fields, text);
+        }
+        let mut found = Vec::new();
+        for field in fields {
+            let mut lists: Vec<&Vec<u32>> = Vec::with_capacity(grams.len());
+            for gram in &grams {
+                match self.trigrams.get(&(*field, *gram)) {
+                    Some(postings) => lists.push(postings),
+                    None => {
+                        lists.clear();
+                        break;
+                    }
+                }
+            }
+            // Start from the rarest trigram to keep the intersections small
+            lists.sort_by_key(|postings| postings.len());
+            let Some((first, rest)) = lists.split_first() else {
+                continue;
+            };
+            let mut ids = first.to_vec();
+            for postings in rest {
+                ids = intersect(&ids, postings);
+            }
+            found = union(&found, &ids);
+        }
+        Some(found)
+    }
+
+    // Positions of records whose `field` is `text` (uppercased and trimmed), in
+    // order, or None when the field isn't indexed by whole value.
+    pub fn lookup_exact(&self, field: Field, text: &str) -> Option<Vec<u32>> {
+        if !KEY_FIELDS.contains(&field) {
+            return None;
+        }
+        let postings = self.keys.get(&field).and_then(|keys| keys.get(text));
+        Some(postings.cloned().unwrap_or_default())
+    }
+
+    // Like `lookup`, for text too short for trigrams: the records holding a
+    // whole value that contains `text`. None unless every field is a key
+    // field.
+    fn lookup_short(&self, fields: &[Field], text: &str) -> Option<Vec<u32>> {
+        // Keys are trimmed, so surrounding spaces could only match the values
+        if text.is_empty() || text.trim() != text {
+            return None;
+        }
+        let mut found = Vec::new();
+        for field in fields {
+            found.extend(self.lookup_exact(*field, text)?);
+            for (key, postings) in self.keys.get(field).into_iter().flatten() {
+                if key != text && key.contains(text) {
+                    found.extend_from_slice(postings);
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        Some(found)
+    }
+
+    // Positions of the records of this table matching `query`, in order.
+    pub fn search<R: Searchable>(&self, records: &[R], query: &Query) -> Vec<usize> {
+        let ids: Vec<usize> = match query.candidates::<R>(self) {
+            Some(ids) => ids.into_iter().map(|id| id as usize).collect(),
+            None => (0..records.len()).collect(),
+        };
+        ids.into_iter()
+            .filter(|&id| {
+                query.matches(&IndexedRecord {
+                    record: &records[id],
+                    table: self,
+                    id,
+                })
+            })
+            .collect()
+    }
+}
+
+// A record read through its table's precomputed values.
+struct IndexedRecord<'a, R> {
+    record: &'a R,
+    table: &'a TableIndex,
+    id: usize,
+}
+
+impl<R: Searchable> Searchable for IndexedRecord<'_, R> {
+    const DEFAULT_FIELDS: &'static [Field] = R::DEFAULT_FIELDS;
+
+    fn field_values(&self, field: Field) -> Option<Vec<Cow<'_, str>>> {
+        let rows = self.table.values.get(&field)?;
+        Some(
+            rows[self.id]
+                .iter()
+                .map(|value| Cow::from(value.as_str()))
+                .collect(),
+        )
+    }
+
+    fn field_date(&self, field: Field) -> Option<NaiveDate> {
+        self.record.field_date(field)
+    }
+}
+
+fn trigrams(text: &str) -> impl Iterator<Item = [char; 3]> + '_ {
+    let chars: Vec<char> = text.chars().collect();
+    (0..chars.len().saturating_sub(2)).map(move |i| [chars[i], chars[i + 1], chars[i + 2]])
+}
+
+// Intersection of two sorted lists.
+pub fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+// Union of two sorted lists.
+pub fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                out.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                out.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Assembly;
+
+    fn index() -> TableIndex {
+        let assemblies: Vec<Assembly> = [
+            ("1BBK1234567890", "123456", "Atom 2000, Black"),
+            ("1BBK0987654321", "12", "Atom 2000, White"),
+            ("2CCK1234554321", " 654321 ", "Atom 3000"),
+        ]
+        .map(|(serial, sales_order, description)| Assembly {
+            serial_number: serial.to_string(),
+            sales_order: sales_order.to_string(),
+            description: description.to_string(),
+            ..Assembly::default()
+        })
+        .to_vec();
+        TableIndex::build(&assemblies)
+    }
+
+    #[test]
+    fn trigram_lookup_finds_substrings() {
+        let index = index();
+        assert_eq!(index.lookup(&[Field::Serial], "1BBK"), Some(vec![0, 1]));
+        assert_eq!(index.lookup(&[Field::Serial], "4321"), Some(vec![1, 2]));
+        assert_eq!(index.lookup(&[Field::Serial], "XYZ"), Some(vec![]));
+        assert_eq!(
+            index.lookup(&[Field::Serial, Field::Description], "3000"),
+            Some(vec![2])
+        );
+    }
+
+    #[test]
+    fn short_text_searches_key_values() {
+        let index = index();
+        assert_eq!(index.lookup(&[Field::SalesOrder], "12"), Some(vec![0, 1]));
+        assert_eq!(index.lookup(&[Field::SalesOrder], "65"), Some(vec![2]));
+        assert_eq!(index.lookup(&[Field::SalesOrder], "9"), Some(vec![]));
+    }
+
+    #[test]
+    fn short_text_in_other_fields_needs_a_scan() {
+        let index = index();
+        assert_eq!(index.lookup(&[Field::Description], "30"), None);
+        assert_eq!(
+            index.lookup(&[Field::Serial, Field::Description], "1B"),
+            None
+        );
+        assert_eq!(index.lookup(&[Field::SalesOrder], " 6"), None);
+    }
+
+    #[test]
+    fn exact_lookup_matches_whole_trimmed_values() {
+        let index = index();
+        assert_eq!(index.lookup_exact(Field::SalesOrder, "12"), Some(vec![1]));
+        assert_eq!(
+            index.lookup_exact(Field::SalesOrder, "654321"),
+            Some(vec![2])
+        );
+        assert_eq!(index.lookup_exact(Field::SalesOrder, "1234"), Some(vec![]));
+        assert_eq!(index.lookup_exact(Field::Description, "ATOM 3000"), None);
+    }
+}
@@ -0,0 +1,408 @@
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{AssembliesRoot, Assembly, Drive, DrivesRoot, ZendeskRoot, ZendeskTicket};
+
+// A database file is a single object holding one array of records under `KEY`.
+pub trait Database: Sized {
+    const KEY: &'static str;
+    type Record: DeserializeOwned + TextFields;
+
+    fn from_records(records: Vec<Self::Record>) -> Self;
+}
+
+// The text fields of a record by name, for clearing null values after loading.
+pub trait TextFields {
+    fn text_fields_mut(&mut self) -> Vec<(&'static str, &mut String)>;
+}
+
+impl TextFields for Assembly {
+    fn text_fields_mut(&mut self) -> Vec<(&'static str, &mut String)> {
+        vec![
+            ("SerialNumber", &mut self.serial_number),
+            ("BuiltBy", &mut self.built_by),
+            ("Description", &mut self.description),
+            ("SalesOrder", &mut self.sales_order),
+        ]
+    }
+}
+
+impl TextFields for Drive {
+    fn text_fields_mut(&mut self) -> Vec<(&'static str, &mut String)> {
+        vec![
+            ("Enclosure SN", &mut self.enclosure_sn),
+            ("Drive SN", &mut self.drive_sn),
+            ("Drive Manufacturer", &mut self.drive_manufacturer),
+            ("Model", &mut self.model),
+            ("Part Number", &mut self.part_number),
+        ]
+    }
+}
+
+impl TextFields for ZendeskTicket {
+    fn text_fields_mut(&mut self) -> Vec<(&'static str, &mut String)> {
+        vec![
+            ("Serial", &mut self.serial),
+            ("Drive", &mut self.drive),
+            ("Assembler", &mut self.assembler),
+            ("OldDiagnosis", &mut self.old_diagnosis),
+            ("NewDiagnosis", &mut self.new_diagnosis),
+        ]
+    }
+}
+
+// Empties every field holding one of `null_values`, ignoring case and
+// surrounding whitespace, counting the values cleared in each field.
+fn clear_null_values<R: TextFields>(
+    record: &mut R,
+    null_values: &[String],
+    cleared: &mut BTreeMap<&'static str, usize>,
+) {
+    for (name, field) in record.text_fields_mut() {
+        let value = field.trim();
+        if null_values
+            .iter()
+            .any(|null| null.trim().eq_ignore_ascii_case(value))
+        {
+            field.clear();
+            *cleared.entry(name).or_default() += 1;
+        }
+    }
+}
+
+impl Database for AssembliesRoot {
+    const KEY: &'static str = "ASM";
+    type Record = Assembly;
+
+    fn from_records(records: Vec<Assembly>) -> Self {
+        AssembliesRoot { asm: records }
+    }
+}
+
+impl Database for DrivesRoot {
+    const KEY: &'static str = "DWE";
+    type Record = Drive;
+
+    fn from_records(records: Vec<Drive>) -> Self {
+        DrivesRoot { drive: records }
+    }
+}
+
+impl Database for ZendeskRoot {
+    const KEY: &'static str = "ZEN";
+    type Record = ZendeskTicket;
+
+    fn from_records(mut records: Vec<ZendeskTicket>) -> Self {
+        for ticket in &mut records {
+            ticket.find_diagnosis_date();
+        }
+        ZendeskRoot {
+            zendesk_ticket: records,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    MissingRoot {
+        path: PathBuf,
+        key: &'static str,
+    },
+    Record {
+        path: PathBuf,
+        index: usize,
+        field: Option<String>,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { path, source } => {
+                write!(f, "Could not read {}: {}", path.display(), source)
+            }
+            LoadError::Syntax {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "{}:{}:{}: malformed JSON: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+            LoadError::MissingRoot { path, key } => {
+                write!(f, "{}: missing top-level \"{}\" array", path.display(), key)
+            }
+            LoadError::Record {
+                path,
+                index,
+                field,
+                line,
+                column,
+                message,
+            } => {
+                write!(
+                    f,
+                    "{}:{}:{}: record {}",
+                    path.display(),
+                    line,
+                    column,
+                    index
+                )?;
+                if let Some(field) = field {
+                    write!(f, ", field \"{}\"", field)?;
+                }
+                write!(f, ": {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Records dropped while loading in lenient mode, and how many values were
+// cleared as null in each field.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub dropped: Vec<LoadError>,
+    pub cleared: BTreeMap<&'static str, usize>,
+}
+
+// Loads a database file. In strict mode the first bad record is an error; in
+// lenient mode bad records are skipped and returned in the report instead.
+// Fields holding one of `null_values` are loaded as empty.
+pub fn load_json<T: Database>(
+    path: &Path,
+    lenient: bool,
+    null_values: &[String],
+) -> Result<(T, LoadReport), LoadError> {
+    let data = std::fs::read_to_string(path).map_err(|source| LoadError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    // Only the records array is parsed, so other top-level keys can hold
+    // anything
+    let mut root: HashMap<String, &RawValue> =
+        serde_json::from_str(&data).map_err(|err| LoadError::Syntax {
+            path: path.to_path_buf(),
+            line: err.line(),
+            column: err.column(),
+            message: strip_position(&err),
+        })?;
+    let raw_root = root.remove(T::KEY).ok_or(LoadError::MissingRoot {
+        path: path.to_path_buf(),
+        key: T::KEY,
+    })?;
+    let raw_records: Vec<&RawValue> = serde_json::from_str(raw_root.get()).map_err(|err| {
+        let (line, column) = file_position(&data, raw_root.get(), &err);
+        LoadError::Syntax {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message: strip_position(&err),
+        }
+    })?;
+
+    let mut records = Vec::with_capacity(raw_records.len());
+    let mut report = LoadReport::default();
+    for (index, raw) in raw_records.into_iter().enumerate() {
+        match parse_record::<T::Record>(&data, raw) {
+            Ok(mut record) => {
+                clear_null_values(&mut record, null_values, &mut report.cleared);
+                records.push(record);
+            }
+            Err((field, line, column, message)) => {
+                let err = LoadError::Record {
+                    path: path.to_path_buf(),
+                    index,
+                    field,
+                    line,
+                    column,
+                    message,
+                };
+                if !lenient {
+                    return Err(err);
+                }
+                report.dropped.push(err);
+            }
+        }
+    }
+    Ok((T::from_records(records), report))
+}
+
+// Deserializes one record, mapping any error to (field, line, column, message)
+// with the position translated back into the whole file.
+fn parse_record<R: DeserializeOwned>(
+    data: &str,
+    raw: &RawValue,
+) -> Result<R, (Option<String>, usize, usize, String)> {
+    let text = raw.get();
+    let deserializer = &mut serde_json::Deserializer::from_str(text);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let field = match err.path().iter().next_back() {
+            Some(serde_path_to_error::Segment::Map { key }) => Some(key.clone()),
+            _ => missing_field_name(err.inner()),
+        };
+        let (line, column) = file_position(data, text, err.inner());
+        (field, line, column, strip_position(err.inner()))
+    })
+}
+
+// Translates the position of an error in `text`, a slice of `data`, into a
+// line and column of the whole file.
+fn file_position(data: &str, text: &str, err: &serde_json::Error) -> (usize, usize) {
+    let offset = text.as_ptr() as usize - data.as_ptr() as usize;
+    let (start_line, start_column) = line_column(data, offset);
+    if err.line() <= 1 {
+        (start_line, start_column + err.column().saturating_sub(1))
+    } else {
+        (start_line + err.line() - 1, err.column())
+    }
+}
+
+fn missing_field_name(err: &serde_json::Error) -> Option<String> {
+    let message = err.to_string();
+    let rest = message.strip_prefix("missing field `")?;
+    rest.split('`').next().map(str::to_string)
+}
+
+// serde_json appends " at line X column Y"; we report our own position.
+pub fn strip_position(err: &serde_json::Error) -> String {
+    let message = err.to_string();
+    match message.rfind(" at line ") {
+        Some(pos) => message[..pos].to_string(),
+        None => message,
+    }
+}
+
+// 1-based line and column of a byte offset.
+fn line_column(data: &str, offset: usize) -> (usize, usize) {
+    let before = &data[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(pos) => before[pos + 1..].chars().count() + 1,
+        None => before.chars().count() + 1,
+    };
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type RecordError = (Option<String>, usize, usize, String);
+
+    // Parses record `index` of the ASM array in `data`.
+    fn parse_assembly(data: &str, index: usize) -> Result<Assembly, RecordError> {
+        let root: HashMap<String, &RawValue> = serde_json::from_str(data).unwrap();
+        let records: Vec<&RawValue> = serde_json::from_str(root["ASM"].get()).unwrap();
+        parse_record(data, records[index])
+    }
+
+    #[test]
+    fn record_errors_on_the_first_line_are_offset_by_the_record_start() {
+        let data = r#"{"ASM": [{"SerialNumber": "A", "BuiltDate": "x", "BuiltBy": "", "Description": "", "SalesOrder": ""}]}"#;
+        let (field, line, column, message) = parse_assembly(data, 0).unwrap_err();
+        assert_eq!(field.as_deref(), Some("BuiltDate"));
+        assert_eq!(line, 1);
+        // At the bad value's closing quote, as serde_json reports it
+        assert_eq!(column, data.find(r#""x""#).unwrap() + 3);
+        assert!(message.starts_with("invalid type: string \"x\""), "{}", message);
+    }
+
+    #[test]
+    fn record_errors_on_later_lines_keep_their_column() {
+        let data = r#"{
+  "Version": 2,
+  "ASM": [
+    {"SerialNumber": "A", "BuiltDate": 1, "BuiltBy": "", "Description": "", "SalesOrder": ""},
+    {
+      "SerialNumber": "B",
+      "BuiltDate": 1,
+      "BuiltBy": 7,
+      "Description": "",
+      "SalesOrder": ""
+    }
+  ]
+}"#;
+        assert!(parse_assembly(data, 0).is_ok());
+        let (field, line, column, _) = parse_assembly(data, 1).unwrap_err();
+        assert_eq!(field.as_deref(), Some("BuiltBy"));
+        assert_eq!((line, column), (8, 18));
+    }
+
+    #[test]
+    fn missing_fields_are_named() {
+        let data = r#"{"ASM": [{"SerialNumber": "C"}]}"#;
+        let (field, line, column, message) = parse_assembly(data, 0).unwrap_err();
+        assert_eq!(field.as_deref(), Some("BuiltDate"));
+        // At the record's closing brace
+        assert_eq!((line, column), (1, 30));
+        assert_eq!(message, "missing field `BuiltDate`");
+    }
+
+    #[test]
+    fn load_json_ignores_other_keys_and_counts_cleared_values() {
+        let path = std::env::temp_dir()
+            .join(format!("glycli-loader-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"Version": 2, "ASM": [
+                {"SerialNumber": "A", "BuiltDate": 1, "BuiltBy": "None.", "Description": "n/a", "SalesOrder": ""},
+                {"SerialNumber": "B", "BuiltDate": 1, "BuiltBy": " none. ", "Description": "", "SalesOrder": ""}
+            ]}"#,
+        )
+        .unwrap();
+        let null_values = ["None.".to_string(), "N/A".to_string()];
+        let loaded = load_json::<AssembliesRoot>(&path, false, &null_values);
+        std::fs::remove_file(&path).unwrap();
+        let (root, report) = loaded.unwrap();
+        assert_eq!(root.asm.len(), 2);
+        assert!(root
+            .asm
+            .iter()
+            .all(|a| a.built_by.is_empty() && a.description.is_empty()));
+        assert_eq!(
+            report.cleared.into_iter().collect::<Vec<_>>(),
+            [("BuiltBy", 2), ("Description", 1)]
+        );
+    }
+
+    #[test]
+    fn line_column_counts_characters() {
+        let data = "ab\nçd\ne";
+        assert_eq!(line_column(data, 0), (1, 1));
+        assert_eq!(line_column(data, 3), (2, 1));
+        assert_eq!(line_column(data, data.find('d').unwrap()), (2, 2));
+        assert_eq!(line_column(data, data.len()), (3, 2));
+    }
+}
@@ -1,149 +1,127 @@
-use chrono::{Duration, NaiveDate};
+mod audit;
+mod cli;
+mod dates;
+mod editor;
+mod export;
+mod fuzzy;
+mod graph;
+mod history;
+mod index;
+mod loader;
+mod query;
+mod search;
+mod tui;
+mod warranty;
+
+use chrono::NaiveDate;
+use clap::Parser;
+use audit::{Cleared, Severity};
+use cli::{Cli, Command, ExportFormat, OutputFormat};
+use dates::{epoch_millis_to_date, Conversion, DateFormat};
+use editor::{create_editor, default_history_path};
+use graph::{Graph, Node, Record};
+use history::{Recall, SessionHistory};
+use index::SearchIndex;
+use loader::{load_json, Database};
+use query::QueryError;
+use search::{search_all, SearchResults};
+use warranty::{ExpiryGroup, Term, WarrantyRules};
 use colored::*;
-use env_logger;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use rustyline::error::ReadlineError;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::io::{self};
-
-#[allow(non_snake_case)]
-
-fn load_json<T>(filename: &str) -> Result<T, Box<dyn std::error::Error>>
-where
-    T: for<'de> Deserialize<'de> + Default,
-{
-    let data = std::fs::read_to_string(filename)?;
-    match serde_json::from_str(&data) {
-        Ok(value) => Ok(value),
-        Err(err) => {
-            error!("Error deserializing JSON: {}", err);
-            Ok(Default::default())
-        }
-    }
-}
-
-fn search_in_assemblies<'a>(
-    assemblies: &'a AssembliesRoot,
-    search_term: &str,
-) -> Vec<&'a Assembly> {
-    let search_term_upper = search_term.trim().to_uppercase();
-    assemblies
-        .asm
-        .iter()
-        .filter(|assembly| {
-            assembly.serial_number.to_uppercase().contains(&search_term_upper)
-                || assembly.sales_order.to_uppercase().contains(&search_term_upper)
-                || assembly.description.to_uppercase().contains(&search_term_upper)
-        })
-        .collect()
-}
-
-fn search_in_drives<'a>(drives: &'a DrivesRoot, search_term: &str) -> Vec<&'a Drive> {
-    let search_term_upper = search_term.trim().to_uppercase();
-    drives
-        .drive
-        .iter()
-        .filter(|drive| {
-            drive.enclosure_sn.to_uppercase().contains(&search_term_upper)
-                || drive.drive_sn.to_uppercase().contains(&search_term_upper)
-                || drive.drive_manufacturer.to_uppercase().contains(&search_term_upper)
-                || drive.model.to_uppercase().contains(&search_term_upper)
-                || drive.part_number.to_uppercase().contains(&search_term_upper)
-        })
-        .collect()
-}
-
-fn search_in_zendesk<'a>(
-    zendesk: &'a ZendeskRoot,
-    search_term: &str,
-) -> Vec<&'a ZendeskTicket> {
-    let search_term_upper = search_term.trim().to_uppercase();
-    zendesk
-        .zendesk_ticket
-        .iter()
-        .filter(|ticket| {
-            ticket.rma.to_string().to_uppercase().contains(&search_term_upper)
-                || ticket.serial.to_uppercase().contains(&search_term_upper)
-                || ticket.drive.to_uppercase().contains(&search_term_upper)
-                || ticket.old_diagnosis.to_uppercase().contains(&search_term_upper)
-                || ticket.new_diagnosis.to_uppercase().contains(&search_term_upper)
-        })
-        .collect()
-}
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process;
 
 fn print_assembly(
+    out: &mut dyn Write,
     assembly: &Assembly,
     indent: usize,
-    built_by_colors: &HashMap<String, Color>,
-) {
+    palette: &Palette,
+) -> io::Result<()> {
     let indent_str = "    ".repeat(indent + 1);
-    println!("{}", "Assembly:".green().bold());
+    writeln!(out, "{}", "Assembly:".green().bold())?;
 
     if !assembly.serial_number.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Serial Number:".cyan(),
             assembly.serial_number.white()
-        );
+        )?;
     }
 
     if assembly.built_date != 0 {
-        print_build_date_with_warranty(assembly.built_date, indent + 1);
+        print_build_date_with_warranty(out, assembly, indent + 1, palette)?;
     }
 
     if !assembly.built_by.is_empty() {
-        let built_by_color = built_by_colors
+        let built_by_color = palette
+            .built_by_colors
             .get(&assembly.built_by)
             .cloned()
             .unwrap_or(Color::White);
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Built by:".cyan(),
             assembly.built_by.color(built_by_color)
-        );
+        )?;
     }
 
     if !assembly.description.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Description:".cyan(),
             assembly.description.white()
-        );
+        )?;
     }
 
     if !assembly.sales_order.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Sales Order:".cyan(),
             assembly.sales_order.white()
-        );
+        )?;
     }
+    Ok(())
 }
 
-fn print_drive(drive: &Drive, indent: usize, manufacturer_colors: &HashMap<String, Color>) {
+fn print_drive(
+    out: &mut dyn Write,
+    drive: &Drive,
+    indent: usize,
+    manufacturer_colors: &HashMap<String, Color>,
+) -> io::Result<()> {
     let indent_str = "    ".repeat(indent);
-    println!("{}", "Drive:".green().bold());
+    writeln!(out, "{}", "Drive:".green().bold())?;
 
     if !drive.enclosure_sn.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Enclosure SN:".cyan(),
             drive.enclosure_sn.white()
-        );
+        )?;
     }
 
     if !drive.drive_sn.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Drive SN:".cyan(),
             drive.drive_sn.white()
-        );
+        )?;
     }
 
     if !drive.drive_manufacturer.is_empty() {
@@ -151,120 +129,256 @@ fn print_drive(drive: &Drive, indent: usize, manufacturer_colors: &HashMap<Strin
             .get(&drive.drive_manufacturer)
             .cloned()
             .unwrap_or(Color::White);
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Drive Manufacturer:".cyan(),
             drive.drive_manufacturer.color(color)
-        );
+        )?;
     }
 
     if !drive.model.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Model:".cyan(),
             drive.model.white()
-        );
+        )?;
     }
 
     if !drive.part_number.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Part Number:".cyan(),
             drive.part_number.white()
-        );
+        )?;
     }
+    Ok(())
 }
 
-fn print_zendesk_ticket(ticket: &ZendeskTicket, indent: usize) {
+// `assembly` is the assembly shown with the ticket, if any, to flag an
+// assembler that doesn't match its builder and show its warranty when the
+// ticket was opened.
+fn print_zendesk_ticket(
+    out: &mut dyn Write,
+    ticket: &ZendeskTicket,
+    indent: usize,
+    assembly: Option<&Assembly>,
+    palette: &Palette,
+) -> io::Result<()> {
     let indent_str = "    ".repeat(indent);
-    println!("{}", "Zendesk Ticket:".green().bold());
+    writeln!(out, "{}", "Zendesk Ticket:".green().bold())?;
 
     if ticket.rma != 0 {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "RMA:".cyan(),
             ticket.rma.to_string().white()
-        );
+        )?;
+    }
+
+    if let Some(millis) = ticket.opened_date {
+        // Out of range dates are shown as unknown
+        let opened = epoch_millis_to_date(millis);
+        writeln!(
+            out,
+            "{}{} {}",
+            indent_str,
+            "Opened:".cyan(),
+            opened.map_or("unknown".to_string(), |date| date.to_string()).white()
+        )?;
+        let warranty = assembly.and_then(|a| palette.warranty.warranty(a));
+        if let (Some(opened), Some(warranty)) = (opened, warranty) {
+            let as_of = format!("As of {}:", palette.as_of);
+            for (label, date) in [("When opened:", opened), (as_of.as_str(), palette.as_of)] {
+                let status = warranty.describe(date);
+                let status = if warranty.is_active(date) {
+                    status.green().bold()
+                } else {
+                    status.red().bold()
+                };
+                writeln!(out, "{}{} {}", indent_str, label.cyan(), status)?;
+            }
+        }
     }
 
     if !ticket.serial.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Serial:".cyan(),
             ticket.serial.white()
-        );
+        )?;
     }
 
     if !ticket.drive.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Drive:".cyan(),
             ticket.drive.white()
-        );
+        )?;
+    }
+
+    if !ticket.assembler.is_empty() {
+        let color = palette
+            .built_by_colors
+            .get(ticket.assembler.trim())
+            .cloned()
+            .unwrap_or(Color::White);
+        writeln!(
+            out,
+            "{}{} {}",
+            indent_str,
+            "Assembler:".cyan(),
+            ticket.assembler.color(color)
+        )?;
+        if let Some(assembly) = assembly.filter(|a| ticket.assembler_mismatch(a)) {
+            writeln!(
+                out,
+                "{}{}",
+                indent_str,
+                format!("Assembler differs from Built by: {}", assembly.built_by)
+                    .yellow()
+                    .bold()
+            )?;
+        }
     }
 
     if !ticket.old_diagnosis.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "Old Diagnosis:".cyan(),
             ticket.old_diagnosis.white()
-        );
+        )?;
     }
 
     if !ticket.new_diagnosis.is_empty() {
-        println!(
+        writeln!(
+            out,
             "{}{} {}",
             indent_str,
             "New Diagnosis:".cyan(),
             ticket.new_diagnosis.white()
-        );
+        )?;
+    }
+
+    if let Some(date) = ticket.diagnosis_date {
+        writeln!(
+            out,
+            "{}{} {}",
+            indent_str,
+            "Diagnosis Date:".cyan(),
+            date.to_string().white()
+        )?;
     }
+    Ok(())
 }
 
-fn print_build_date_with_warranty(built_date: i64, indent: usize) {
+fn print_build_date_with_warranty(
+    out: &mut dyn Write,
+    assembly: &Assembly,
+    indent: usize,
+    palette: &Palette,
+) -> io::Result<()> {
     let indent_str = "    ".repeat(indent);
-    let days_since_epoch = built_date / 86400000; // Assuming built_date is in milliseconds
-    let dt = NaiveDate::from_ymd(1970, 1, 1) + Duration::days(days_since_epoch);
-    println!(
+    // An out of range date is treated as missing
+    let Some(dt) = epoch_millis_to_date(assembly.built_date) else {
+        return Ok(());
+    };
+    writeln!(
+        out,
         "{}{} {}",
         indent_str,
         "Built Date:".cyan(),
         dt.to_string().white()
-    );
-
-    // Check if the build date is within 3 years
-    let current_date = chrono::Utc::now().naive_utc().date();
-    let warranty_period = Duration::days(365 * 3);
-    if current_date.signed_duration_since(dt) <= warranty_period {
-        println!(
+    )?;
+
+    let Some(warranty) = palette.warranty.warranty(assembly) else {
+        return Ok(());
+    };
+    let today = palette.as_of;
+    if warranty.is_active(today) {
+        writeln!(
+            out,
             "{}{}",
             indent_str,
-            "Drive is under warranty".green().bold()
-        );
+            warranty.describe(today).green().bold()
+        )?;
     } else {
-        println!(
+        writeln!(
+            out,
             "{}{}",
             indent_str,
-            "Drive is out of warranty".red().bold()
-        );
+            warranty.describe(today).red().bold()
+        )?;
     }
+    Ok(())
 }
 
-fn parse_and_print_date(date_str: &str) {
-    if let Ok(mut date) = date_str.parse::<i64>() {
-        date = date / 86400000 + 25569;
-        let dt = NaiveDate::from_ymd_opt(1900, 1, 1).unwrap() + Duration::days(date);
-        println!("{}: {}", "Parsed Date".cyan(), dt.to_string().white());
-    } else {
-        println!("{}", "Invalid date format".red());
+// Reads a date in any supported format and prints it in all of them.
+// Returns false when the input isn't a date.
+fn print_date_conversion(input: &str, from: Option<DateFormat>, output: OutputFormat) -> bool {
+    let conversion = match Conversion::read(input, from) {
+        Ok(conversion) => conversion,
+        Err(message) => {
+            info!("Invalid date: {}", message);
+            eprintln!("{}", format!("Invalid date: {}", message).red().bold());
+            return false;
+        }
+    };
+    match output {
+        OutputFormat::Text => {
+            println!(
+                "{} {} {}: {}",
+                "Read".cyan(),
+                input.trim().white(),
+                format!("as {}", conversion.read_as.name()).cyan(),
+                conversion.date.to_string().white().bold()
+            );
+            for (format, value) in conversion.representations() {
+                let value = value.unwrap_or_else(|| "-".to_string());
+                println!(
+                    "    {} {}",
+                    format!("{:<20}", format!("{}:", format.name())).cyan(),
+                    value.white()
+                );
+            }
+            if let Some((format, date)) = conversion.alternative {
+                println!(
+                    "{}",
+                    format!("As an {} it would be {}.", format.name(), date).dimmed()
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let formats: serde_json::Map<String, serde_json::Value> = conversion
+                .representations()
+                .into_iter()
+                .map(|(format, value)| (format.name().to_string(), value.into()))
+                .collect();
+            let json = serde_json::json!({
+                "Input": input.trim(),
+                "ReadAs": conversion.read_as.name(),
+                "Formats": formats,
+            });
+            match serde_json::to_string_pretty(&json) {
+                Ok(json) => println!("{}", json),
+                Err(err) => error!("Error serializing date: {}", err),
+            }
+        }
     }
+    true
 }
 
 // Assemblies JSON
@@ -326,18 +440,54 @@ pub struct ZendeskRoot {
 pub struct ZendeskTicket {
     #[serde(rename = "RMA")]
     pub rma: i64,
+    // Epoch milliseconds like BuiltDate; missing from older exports
+    #[serde(
+        rename = "OpenedDate",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub opened_date: Option<i64>,
     #[serde(rename = "Serial")]
     pub serial: String,
     #[serde(rename = "Drive")]
     pub drive: String,
+    // Missing from older exports
+    #[serde(rename = "Assembler", default)]
+    pub assembler: String,
     #[serde(rename = "OldDiagnosis")]
     pub old_diagnosis: String,
     #[serde(rename = "NewDiagnosis")]
     pub new_diagnosis: String,
+    // A date written in NewDiagnosis, or else OldDiagnosis, found at load time
+    #[serde(
+        rename = "DiagnosisDate",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub diagnosis_date: Option<NaiveDate>,
+}
+
+impl ZendeskTicket {
+    // Finds a date written in the diagnosis fields, preferring the newer one.
+    pub fn find_diagnosis_date(&mut self) {
+        self.diagnosis_date =
+            dates::find_date(&self.new_diagnosis).or_else(|| dates::find_date(&self.old_diagnosis));
+    }
+
+    // Whether the ticket names a different assembler than the BuiltBy of the
+    // assembly with the ticket's serial number. Blank names never mismatch.
+    pub fn assembler_mismatch(&self, assembly: &Assembly) -> bool {
+        let assembler = self.assembler.trim();
+        let built_by = assembly.built_by.trim();
+        index::key(self.serial.trim()) == index::key(assembly.serial_number.trim())
+            && !assembler.is_empty()
+            && !built_by.is_empty()
+            && !assembler.eq_ignore_ascii_case(built_by)
+    }
 }
 
 fn assign_colors(names: &[&String]) -> HashMap<String, Color> {
-    let color_list = vec![
+    let color_list = [
         Color::Red,
         Color::Green,
         Color::Yellow,
@@ -362,7 +512,7 @@ fn assign_colors(names: &[&String]) -> HashMap<String, Color> {
 
 fn assign_pastel_colors(names: &[&String]) -> HashMap<String, Color> {
     // Define some pastel colors using RGB
-    let pastel_color_values = vec![
+    let pastel_color_values = [
         Color::TrueColor {
             r: 255,
             g: 179,
@@ -414,35 +564,740 @@ fn get_parent_manufacturer_name(
     }
     manufacturer_name.to_string()
 }
-fn main() {
-    env_logger::init();
-    info!("Glyph Database Started. Type Q to quit.");
+
+// Everything loaded from the three database files, and the warranty rules
+// applied to them.
+pub struct Databases {
+    pub assemblies: AssembliesRoot,
+    pub drives: DrivesRoot,
+    pub zendesk: ZendeskRoot,
+    pub index: SearchIndex,
+    pub graph: Graph,
+    pub warranty: WarrantyRules,
+}
+
+impl Databases {
+    pub fn new(
+        assemblies: AssembliesRoot,
+        drives: DrivesRoot,
+        zendesk: ZendeskRoot,
+        warranty: WarrantyRules,
+    ) -> Self {
+        let index = SearchIndex::build(&assemblies, &drives, &zendesk);
+        let graph = Graph::build(&assemblies, &drives, &zendesk);
+        Databases {
+            assemblies,
+            drives,
+            zendesk,
+            index,
+            graph,
+            warranty,
+        }
+    }
+
+    pub fn record(&self, node: Node) -> Record<'_> {
+        match node {
+            Node::Assembly(i) => Record::Assembly(&self.assemblies.asm[i]),
+            Node::Drive(i) => Record::Drive(&self.drives.drive[i]),
+            Node::Ticket(i) => Record::Ticket(&self.zendesk.zendesk_ticket[i]),
+        }
+    }
+}
+
+// Counts of unique items shown at startup.
+pub struct UniqueCounts {
+    pub manufacturers: usize,
+    pub builders: usize,
+    pub enclosures: usize,
+}
+
+// Colors and warranty rules used when printing records.
+struct Palette<'a> {
+    built_by_colors: HashMap<String, Color>,
+    manufacturer_colors: HashMap<String, Color>,
+    warranty: &'a WarrantyRules,
+    // Date warranties are evaluated at
+    as_of: NaiveDate,
+}
+
+enum SearchOutcome {
+    Found,
+    NotFound,
+    InvalidQuery(String),
+    // Only part of the results were printed
+    MoreResults { first: usize, last: usize, total: usize },
+}
+
+impl SearchOutcome {
+    fn message(&self) -> String {
+        match self {
+            SearchOutcome::Found => String::new(),
+            SearchOutcome::NotFound => "No matching results found.".to_string(),
+            SearchOutcome::InvalidQuery(message) => format!("Invalid search: {}", message),
+            SearchOutcome::MoreResults { first, last, total } => format!(
+                "Showing results {}-{} of {}; use --offset {} to see more.",
+                first, last, total, last
+            ),
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self {
+            SearchOutcome::Found => exit_code::FOUND,
+            SearchOutcome::NotFound => exit_code::NOT_FOUND,
+            SearchOutcome::InvalidQuery(_) => exit_code::INVALID_QUERY,
+            SearchOutcome::MoreResults { .. } => exit_code::TOO_MANY,
+        }
+    }
+}
+
+impl From<QueryError> for SearchOutcome {
+    fn from(err: QueryError) -> Self {
+        SearchOutcome::InvalidQuery(format!("{}\n{}", err, err.pointer()))
+    }
+}
+
+// Process exit codes. Clap uses 2 for usage errors.
+mod exit_code {
+    pub const FOUND: i32 = 0;
+    pub const NOT_FOUND: i32 = 1;
+    pub const INVALID_QUERY: i32 = 2;
+    // More results than were printed
+    pub const TOO_MANY: i32 = 3;
+    pub const LOAD_FAILED: i32 = 4;
+    pub const WRITE_FAILED: i32 = 5;
+    // The audit found errors
+    pub const AUDIT_FAILED: i32 = 6;
+    // The terminal UI or line editor failed
+    pub const UI_FAILED: i32 = 7;
+}
+
+// Loads one database, adding the null values it cleared to `cleared`.
+fn load_database<T: Database>(
+    path: &Path,
+    name: &'static str,
+    cli: &Cli,
+    quiet: bool,
+    cleared: &mut Vec<Cleared>,
+) -> T {
+    info!("Loading {} Database...", name);
+    if !quiet {
+        println!("{}", format!("Loading {} Database...", name).yellow());
+    }
+    match load_json(path, cli.lenient, &cli.null_values) {
+        Ok((value, report)) => {
+            info!("{} Database Loaded.", name);
+            if !quiet {
+                println!("{}", format!("{} Database Loaded.", name).green());
+            }
+            if !report.dropped.is_empty() {
+                warn!(
+                    "Dropped {} malformed records from {} Database",
+                    report.dropped.len(),
+                    name
+                );
+                eprintln!(
+                    "{}",
+                    format!(
+                        "Dropped {} malformed records from {} Database:",
+                        report.dropped.len(),
+                        name
+                    )
+                    .yellow()
+                    .bold()
+                );
+                for err in &report.dropped {
+                    eprintln!("    {}", err.to_string().yellow());
+                }
+            }
+            for (field, count) in report.cleared {
+                debug!("Cleared {} null {} values in {} Database", count, field, name);
+                cleared.push(Cleared {
+                    database: name,
+                    field,
+                    count,
+                });
+            }
+            value
+        }
+        Err(err) => {
+            error!("Failed to load {} Database: {}", name, err);
+            eprintln!(
+                "{}",
+                format!("Failed to load {} Database: {}", name, err).red().bold()
+            );
+            process::exit(exit_code::LOAD_FAILED);
+        }
+    }
+}
+
+// Loads the warranty rules file. Without one every assembly gets the default
+// three-year term; a file given explicitly must exist.
+fn load_warranty_rules(cli: &Cli) -> WarrantyRules {
+    let path = cli.warranty_rules_path();
+    if cli.warranty_rules.is_none() && !path.exists() {
+        debug!("No warranty rules at {}; using the default term", path.display());
+        return WarrantyRules::default();
+    }
+    match WarrantyRules::load(&path) {
+        Ok(rules) => {
+            info!("Warranty rules loaded from {}", path.display());
+            rules
+        }
+        Err(err) => {
+            error!("Failed to load warranty rules: {}", err);
+            eprintln!(
+                "{}",
+                format!("Failed to load warranty rules: {}", err).red().bold()
+            );
+            process::exit(exit_code::LOAD_FAILED);
+        }
+    }
+}
+
+// Prints search results in the selected output format. In text output each
+// top-level record is labelled `#set.record` when a result set number is given.
+fn print_results(
+    out: &mut dyn Write,
+    results: &SearchResults,
+    palette: &Palette,
+    output: OutputFormat,
+    set: Option<usize>,
+) -> io::Result<()> {
+    let mut record = results.offset;
+    let mut label = || {
+        record += 1;
+        set.map(|set| format!("#{}.{}", set, record).dimmed())
+    };
+    match output {
+        OutputFormat::Text => {
+            for found in &results.assemblies {
+                if let Some(label) = label() {
+                    writeln!(out, "{}", label)?;
+                }
+                print_assembly(out, found.assembly, 0, palette)?;
+                for drive in &found.drives {
+                    print_drive(out, drive, 1, &palette.manufacturer_colors)?;
+                }
+                for ticket in &found.tickets {
+                    print_zendesk_ticket(out, ticket, 1, Some(found.assembly), palette)?;
+                }
+            }
+            for found in &results.drives {
+                if let Some(label) = label() {
+                    writeln!(out, "{}", label)?;
+                }
+                print_drive(out, found.drive, 0, &palette.manufacturer_colors)?;
+                if let Some(assembly) = found.enclosure {
+                    print_assembly(out, assembly, 0, palette)?;
+                }
+                if !found.siblings.is_empty() {
+                    writeln!(out, "{}", "Other drives in this enclosure:".dimmed())?;
+                }
+                for drive in &found.siblings {
+                    print_drive(out, drive, 1, &palette.manufacturer_colors)?;
+                }
+                for ticket in &found.tickets {
+                    print_zendesk_ticket(out, ticket, 1, found.enclosure, palette)?;
+                }
+            }
+            for found in &results.tickets {
+                if let Some(label) = label() {
+                    writeln!(out, "{}", label)?;
+                }
+                print_zendesk_ticket(out, found.ticket, 0, found.assembly, palette)?;
+                if let Some(assembly) = found.assembly {
+                    print_assembly(out, assembly, 0, palette)?;
+                }
+                if let Some(drive) = found.drive {
+                    print_drive(out, drive, 1, &palette.manufacturer_colors)?;
+                }
+                if !found.tickets.is_empty() {
+                    writeln!(out, "{}", "Other tickets for this enclosure and its drives:".dimmed())?;
+                }
+                for ticket in &found.tickets {
+                    print_zendesk_ticket(out, ticket, 1, found.assembly, palette)?;
+                }
+            }
+        }
+        OutputFormat::Json => match serde_json::to_string_pretty(results) {
+            Ok(json) => writeln!(out, "{}", json)?,
+            Err(err) => error!("Error serializing results: {}", err),
+        },
+    }
+    Ok(())
+}
+
+// Prints results in the interactive prompt, where a failed write has nowhere
+// better to go than the log.
+fn show_results(results: &SearchResults, palette: &Palette, output: OutputFormat, set: Option<usize>) {
+    if let Err(err) = print_results(&mut io::stdout().lock(), results, palette, output, set) {
+        error!("Error printing results: {}", err);
+    }
+}
+
+// Prints how many records matched in each database.
+fn print_summary(results: &SearchResults) {
+    println!("{}", summary_line(results).cyan().bold());
+}
+
+fn summary_line(results: &SearchResults) -> String {
+    format!(
+        "Found {} assemblies, {} drives and {} Zendesk tickets.",
+        results.counts.assemblies, results.counts.drives, results.counts.tickets
+    )
+}
+
+// A result set shown one page at a time in the interactive prompt.
+struct Pager<'a> {
+    set: usize,
+    results: SearchResults<'a>,
+    page_size: usize,
+    offset: usize,
+}
+
+impl<'a> Pager<'a> {
+    fn new(set: usize, results: SearchResults<'a>, page_size: usize) -> Self {
+        Pager {
+            set,
+            results,
+            page_size,
+            offset: 0,
+        }
+    }
+
+    fn show(&self, palette: &Palette, output: OutputFormat) {
+        let page = self.results.page(self.offset, self.page_size);
+        show_results(&page, palette, output, Some(self.set));
+        if self.results.len() > self.page_size {
+            println!(
+                "{}",
+                format!(
+                    "Showing results {}-{} of {}. Type n for the next page, p for the previous page or all for every result.",
+                    self.offset + 1,
+                    self.offset + page.len(),
+                    self.results.len()
+                )
+                .cyan()
+            );
+        }
+    }
+
+    fn next_page(&mut self) -> bool {
+        if self.offset + self.page_size >= self.results.len() {
+            return false;
+        }
+        self.offset += self.page_size;
+        true
+    }
+
+    fn previous_page(&mut self) -> bool {
+        if self.offset == 0 {
+            return false;
+        }
+        self.offset = self.offset.saturating_sub(self.page_size);
+        true
+    }
+
+    fn show_all(&self, palette: &Palette, output: OutputFormat) {
+        show_results(&self.results, palette, output, Some(self.set));
+    }
+}
+
+// Lists recent searches with their result counts.
+fn print_history(history: &SessionHistory) {
+    let mut any = false;
+    for entry in history.recent(20) {
+        any = true;
+        println!(
+            "{} {} ({} results)",
+            format!("#{}", entry.number).cyan(),
+            entry.query.white(),
+            entry.results.len()
+        );
+    }
+    if !any {
+        println!("{}", "No searches yet.".yellow());
+    }
+}
+
+// Prints the audit report, with up to `examples` records per check.
+fn run_audit(dbs: &Databases, cleared: &[Cleared], output: OutputFormat, examples: usize) -> i32 {
+    let checks = audit::audit(dbs, cleared);
+    let count = |severity: Severity| -> usize {
+        checks
+            .iter()
+            .filter(|check| check.severity == severity)
+            .map(|check| check.findings.len())
+            .sum()
+    };
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+
+    match output {
+        OutputFormat::Text => {
+            println!(
+                "{}",
+                format!(
+                    "Audit of {} assemblies, {} drives and {} Zendesk tickets:",
+                    dbs.assemblies.asm.len(),
+                    dbs.drives.drive.len(),
+                    dbs.zendesk.zendesk_ticket.len()
+                )
+                .cyan()
+                .bold()
+            );
+            for check in &checks {
+                let severity = match (check.findings.is_empty(), check.severity) {
+                    (true, _) => "OK     ".green(),
+                    (false, Severity::Error) => "ERROR  ".red().bold(),
+                    (false, Severity::Warning) => "WARNING".yellow().bold(),
+                };
+                println!("{} {}: {}", severity, check.name, check.findings.len());
+                for found in check.findings.iter().take(examples) {
+                    println!("    {}: {}", found.record.label().white(), found.detail);
+                }
+                if check.findings.len() > examples {
+                    println!(
+                        "    {}",
+                        format!("... and {} more", check.findings.len() - examples).dimmed()
+                    );
+                }
+                for cleared in &check.cleared {
+                    println!(
+                        "    {}",
+                        format!(
+                            "{} {} values in {} loaded as empty (--null-value)",
+                            cleared.count, cleared.field, cleared.database
+                        )
+                        .dimmed()
+                    );
+                }
+            }
+            let summary = format!("Found {} errors and {} warnings.", errors, warnings);
+            info!("{}", summary);
+            if errors > 0 {
+                println!("{}", summary.red().bold());
+            } else {
+                println!("{}", summary.green().bold());
+            }
+        }
+        OutputFormat::Json => match serde_json::to_string_pretty(&checks) {
+            Ok(json) => println!("{}", json),
+            Err(err) => error!("Error serializing audit: {}", err),
+        },
+    }
+
+    if errors > 0 {
+        exit_code::AUDIT_FAILED
+    } else {
+        exit_code::FOUND
+    }
+}
+
+// Prints the assemblies whose warranty ends within `within` of `as_of`, by
+// sales order and description, or writes them as CSV or TSV.
+fn run_warranty_report(
+    dbs: &Databases,
+    as_of: NaiveDate,
+    within: Term,
+    output: OutputFormat,
+    format: Option<ExportFormat>,
+    out: Option<&Path>,
+) -> i32 {
+    let groups = warranty::expiry_report(&dbs.assemblies.asm, &dbs.warranty, as_of, within);
+    if let Some(format) = format {
+        if let Err(err) = export::export_expiry_report(&groups, &dbs.warranty, format, out) {
+            error!("Export failed: {}", err);
+            eprintln!("{}", format!("Export failed: {}", err).red().bold());
+            return exit_code::WRITE_FAILED;
+        }
+    } else {
+        match output {
+            OutputFormat::Text => print_expiry_report(&groups, as_of, within),
+            OutputFormat::Json => match serde_json::to_string_pretty(&groups) {
+                Ok(json) => println!("{}", json),
+                Err(err) => error!("Error serializing warranty report: {}", err),
+            },
+        }
+    }
+
+    if groups.is_empty() {
+        exit_code::NOT_FOUND
+    } else {
+        exit_code::FOUND
+    }
+}
+
+fn print_expiry_report(groups: &[ExpiryGroup], as_of: NaiveDate, within: Term) {
     println!(
         "{}",
-        "Glyph Database Started. Type Q to quit.".green().bold()
+        format!(
+            "Warranties ending within {} either side of {}:",
+            within, as_of
+        )
+        .cyan()
+        .bold()
     );
+    let or_none = |value: &str| {
+        if value.is_empty() {
+            "(none)".to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    for group in groups {
+        println!(
+            "{} {}, {}: {} expiring, {} expired",
+            "Sales Order".green().bold(),
+            or_none(group.sales_order).green().bold(),
+            or_none(group.description).green().bold(),
+            group.expiring,
+            group.expired
+        );
+        for entry in &group.assemblies {
+            let status = entry.warranty.describe(as_of);
+            let status = if entry.warranty.is_active(as_of) {
+                status.green()
+            } else {
+                status.red()
+            };
+            println!("    {} {}", entry.assembly.serial_number.white(), status);
+        }
+        for assembly in &group.unknown {
+            println!(
+                "    {} {}",
+                assembly.serial_number.white(),
+                format!("Unknown build date (BuiltDate {})", assembly.built_date).yellow()
+            );
+        }
+    }
+    let expiring: usize = groups.iter().map(|group| group.expiring).sum();
+    let expired: usize = groups.iter().map(|group| group.expired).sum();
+    let unknown: usize = groups.iter().map(|group| group.unknown.len()).sum();
+    let mut summary = format!(
+        "Found {} expiring and {} expired warranties in {} groups",
+        expiring,
+        expired,
+        groups.len()
+    );
+    if unknown > 0 {
+        summary.push_str(&format!(", and {} assemblies with unknown build dates", unknown));
+    }
+    summary.push('.');
+    info!("{}", summary);
+    println!("{}", summary.cyan().bold());
+}
+
+// Prints the shortest chain of links between two records.
+fn run_path(dbs: &Databases, from: &str, to: &str, output: OutputFormat) -> i32 {
+    for serial in [from, to] {
+        if dbs.graph.find(serial).is_empty() {
+            let message = format!("No assembly, drive or ticket matches `{}`.", serial);
+            info!("{}", message);
+            eprintln!("{}", message.red().bold());
+            return exit_code::NOT_FOUND;
+        }
+    }
+    let Some(path) = dbs.graph.path(from, to) else {
+        let message = format!("{} and {} are not linked.", from, to);
+        info!("{}", message);
+        eprintln!("{}", message.red().bold());
+        return exit_code::NOT_FOUND;
+    };
+
+    let records: Vec<Record> = path.into_iter().map(|node| dbs.record(node)).collect();
+    match output {
+        OutputFormat::Text => {
+            for (i, record) in records.iter().enumerate() {
+                if i == 0 {
+                    println!("{}", record.label().white());
+                } else {
+                    println!("{} {}", "  ->".cyan(), record.label().white());
+                }
+            }
+        }
+        OutputFormat::Json => match serde_json::to_string_pretty(&records) {
+            Ok(json) => println!("{}", json),
+            Err(err) => error!("Error serializing path: {}", err),
+        },
+    }
+    exit_code::FOUND
+}
 
-    info!("Loading Assemblies Database...");
-    println!("{}", "Loading Assemblies Database...".yellow());
-    let assemblers: AssembliesRoot = load_json("ASM.json").unwrap();
-    info!("Assemblies Database Loaded.");
-    println!("{}", "Assemblies Database Loaded.".green());
+// One piped term's entry in JSON output.
+#[derive(Debug, Serialize)]
+struct TermResults<'a> {
+    #[serde(rename = "Term")]
+    term: &'a str,
+    #[serde(rename = "Results", skip_serializing_if = "Option::is_none")]
+    results: Option<SearchResults<'a>>,
+    #[serde(rename = "Error", skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
 
-    info!("Loading Drives with Enclosures Database...");
-    println!("{}", "Loading Drives with Enclosures Database...".yellow());
-    let drive: DrivesRoot = load_json("DWE.json").unwrap();
-    info!("Drives with Enclosures Database Loaded.");
-    println!("{}", "Drives with Enclosures Database Loaded.".green());
+// One-shot mode: searches each term once, printing `limit` results from
+// `offset` (every result when `limit` is 0), and returns the highest exit code,
+// so e.g. any term with more results than shown wins over any term with none.
+// Terms piped in on stdin with JSON output are printed together as one array of
+// TermResults.
+fn run_query(
+    dbs: &Databases,
+    palette: &Palette,
+    terms: &[String],
+    piped: bool,
+    output: OutputFormat,
+    offset: usize,
+    limit: usize,
+) -> i32 {
+    let mut out = io::stdout().lock();
+    let batch = piped && output == OutputFormat::Json;
+    let mut batched = Vec::new();
+    let mut code = exit_code::FOUND;
+    for term in terms {
+        info!("Searching all databases for: {}", term);
+        let outcome = match search_all(dbs, term) {
+            Ok(results) => {
+                let limit = if limit == 0 { results.len() } else { limit };
+                let page = results.page(offset, limit);
+                let shown = page.len();
+                if batch {
+                    batched.push(TermResults {
+                        term,
+                        results: Some(page),
+                        error: None,
+                    });
+                } else if !page.is_empty() || output == OutputFormat::Json {
+                    // JSON output always emits a document, even an empty one
+                    if let Err(err) = print_results(&mut out, &page, palette, output, None) {
+                        let found = if results.is_empty() {
+                            exit_code::NOT_FOUND
+                        } else {
+                            exit_code::FOUND
+                        };
+                        return write_failed(err, code.max(found));
+                    }
+                }
+                if results.is_empty() {
+                    SearchOutcome::NotFound
+                } else if offset + shown < results.len() {
+                    if output == OutputFormat::Text {
+                        eprintln!("{}: {}", term, summary_line(&results));
+                    }
+                    SearchOutcome::MoreResults {
+                        first: offset + 1,
+                        last: offset + shown,
+                        total: results.len(),
+                    }
+                } else {
+                    SearchOutcome::Found
+                }
+            }
+            Err(err) => {
+                if batch {
+                    batched.push(TermResults {
+                        term,
+                        results: None,
+                        error: Some(err.to_string()),
+                    });
+                }
+                SearchOutcome::from(err)
+            }
+        };
+        if !matches!(outcome, SearchOutcome::Found) {
+            eprintln!("{}: {}", term, outcome.message());
+            if let SearchOutcome::NotFound = outcome {
+                if let Some(suggestion) = fuzzy::did_you_mean(dbs, term) {
+                    eprintln!("{}: {}", term, suggestion);
+                }
+            }
+        }
+        code = code.max(outcome.exit_code());
+    }
+    if batch {
+        match serde_json::to_string_pretty(&batched) {
+            Ok(json) => {
+                if let Err(err) = writeln!(out, "{}", json) {
+                    return write_failed(err, code);
+                }
+            }
+            Err(err) => error!("Error serializing results: {}", err),
+        }
+    }
+    if let Err(err) = out.flush() {
+        return write_failed(err, code);
+    }
+    code
+}
 
-    info!("Loading Zendesk Database...");
-    println!("{}", "Loading Zendesk Database...".yellow());
-    let zendesk_ticket: ZendeskRoot = load_json("ZEN.json").unwrap();
-    info!("Zendesk Database Loaded.");
-    println!("{}", "Zendesk Database Loaded.".green());
+// Exit code after one-shot output failed to write. A reader that stops early,
+// like `head`, isn't an error: the searches so far decide the code.
+fn write_failed(err: io::Error, code: i32) -> i32 {
+    if err.kind() == io::ErrorKind::BrokenPipe {
+        debug!("Output closed early: {}", err);
+        return code;
+    }
+    error!("Failed to write results: {}", err);
+    eprintln!("{}", format!("Failed to write results: {}", err).red().bold());
+    exit_code::WRITE_FAILED
+}
+
+// Terms for one-shot mode come from the arguments, or one per line from stdin
+// when it is piped.
+fn query_terms(term: &[String]) -> Vec<String> {
+    if !term.is_empty() {
+        return vec![term.join(" ")];
+    }
+    if io::stdin().is_terminal() {
+        eprintln!("{}", "No search term given.".red().bold());
+        process::exit(exit_code::INVALID_QUERY);
+    }
+    let terms: Vec<String> = io::stdin()
+        .lock()
+        .lines()
+        .map_while(Result::ok)
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    if terms.is_empty() {
+        eprintln!("{}", "No search terms on stdin.".red().bold());
+        process::exit(exit_code::INVALID_QUERY);
+    }
+    terms
+}
+
+fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    let quiet = cli.command.is_some();
+    info!("Glyph Database Started. Type Q to quit.");
+    if !quiet {
+        println!(
+            "{}",
+            "Glyph Database Started. Type Q to quit.".green().bold()
+        );
+    }
+
+    // The date converter doesn't need the databases
+    if let Some(Command::Date { value, from }) = &cli.command {
+        let code = if print_date_conversion(value, *from, cli.output) {
+            exit_code::FOUND
+        } else {
+            exit_code::INVALID_QUERY
+        };
+        process::exit(code);
+    }
+
+    let mut cleared = Vec::new();
+    let dbs = Databases::new(
+        load_database(&cli.assemblies_path(), "Assemblies", &cli, quiet, &mut cleared),
+        load_database(&cli.drives_path(), "Drives with Enclosures", &cli, quiet, &mut cleared),
+        load_database(&cli.zendesk_path(), "Zendesk", &cli, quiet, &mut cleared),
+        load_warranty_rules(&cli),
+    );
 
     // Count drive manufacturers
     let mut manufacturer_counts: HashMap<String, usize> = HashMap::new();
-    for drive in &drive.drive {
+    for drive in &dbs.drives.drive {
         let manufacturer = drive.drive_manufacturer.trim().to_string();
         *manufacturer_counts.entry(manufacturer).or_insert(0) += 1;
     }
@@ -450,7 +1305,7 @@ fn main() {
     // Convert HashMap to Vec<(String, usize)> and sort by counts descending
     let mut manufacturer_counts_vec: Vec<(String, usize)> =
         manufacturer_counts.into_iter().collect();
-    manufacturer_counts_vec.sort_by(|a, b| b.1.cmp(&a.1));
+    manufacturer_counts_vec.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     // Take top 15
     let top_manufacturers = &manufacturer_counts_vec[..15.min(manufacturer_counts_vec.len())];
@@ -465,7 +1320,7 @@ fn main() {
     // Build a mapping from manufacturer names to parent names
     let mut manufacturer_to_parent: HashMap<String, String> = HashMap::new();
     for (manufacturer, _) in &manufacturer_counts_vec {
-        let parent = get_parent_manufacturer_name(&manufacturer, &parent_names_set);
+        let parent = get_parent_manufacturer_name(manufacturer, &parent_names_set);
         manufacturer_to_parent.insert(manufacturer.clone(), parent);
     }
 
@@ -475,7 +1330,7 @@ fn main() {
 
     // Count built_by
     let mut built_by_counts: HashMap<String, usize> = HashMap::new();
-    for assembly in &assemblers.asm {
+    for assembly in &dbs.assemblies.asm {
         let built_by = assembly.built_by.trim().to_string();
         *built_by_counts.entry(built_by).or_insert(0) += 1;
     }
@@ -483,7 +1338,7 @@ fn main() {
     // Convert HashMap to Vec<(String, usize)> and sort by counts descending
     let mut built_by_counts_vec: Vec<(String, usize)> =
         built_by_counts.into_iter().collect();
-    built_by_counts_vec.sort_by(|a, b| b.1.cmp(&a.1));
+    built_by_counts_vec.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     // Take top 15
     let top_built_bys = &built_by_counts_vec[..15.min(built_by_counts_vec.len())];
@@ -494,18 +1349,104 @@ fn main() {
     // Assign pastel colors to top built_bys
     let built_by_colors = assign_pastel_colors(&built_bys);
 
+    let palette = Palette {
+        built_by_colors,
+        manufacturer_colors: parent_colors,
+        warranty: &dbs.warranty,
+        as_of: cli.as_of(),
+    };
+
     // Count unique Enclosure S/N's
-    let unique_enclosure_sns: HashSet<String> =
-        drive.drive.iter().map(|d| d.enclosure_sn.clone()).collect();
+    let unique_enclosure_sns: HashSet<&String> =
+        dbs.drives.drive.iter().map(|d| &d.enclosure_sn).collect();
+
+    let unique_counts = UniqueCounts {
+        manufacturers: manufacturer_counts_vec.len(),
+        builders: built_by_counts_vec.len(),
+        enclosures: unique_enclosure_sns.len(),
+    };
+
+    match &cli.command {
+        Some(Command::Query {
+            term,
+            offset,
+            limit,
+        }) => {
+            let terms = query_terms(term);
+            let limit = limit.unwrap_or(cli.page_size);
+            process::exit(run_query(
+                &dbs,
+                &palette,
+                &terms,
+                term.is_empty(),
+                cli.output,
+                *offset,
+                limit,
+            ));
+        }
+        Some(Command::Export {
+            table,
+            format,
+            out,
+            term,
+        }) => {
+            let exported = if term.is_empty() {
+                export::export_all(&dbs, *table, *format, out.as_deref())
+            } else {
+                match search_all(&dbs, &term.join(" ")) {
+                    Ok(results) => export::export(&results, *table, *format, out.as_deref()),
+                    Err(err) => {
+                        let outcome = SearchOutcome::from(err);
+                        eprintln!("{}", outcome.message().red().bold());
+                        process::exit(outcome.exit_code());
+                    }
+                }
+            };
+            if let Err(err) = exported {
+                error!("Export failed: {}", err);
+                eprintln!("{}", format!("Export failed: {}", err).red().bold());
+                process::exit(exit_code::WRITE_FAILED);
+            }
+            process::exit(exit_code::FOUND);
+        }
+        Some(Command::Audit { examples }) => {
+            process::exit(run_audit(&dbs, &cleared, cli.output, *examples));
+        }
+        Some(Command::WarrantyReport {
+            within,
+            format,
+            out,
+        }) => {
+            process::exit(run_warranty_report(
+                &dbs,
+                cli.as_of(),
+                *within,
+                cli.output,
+                *format,
+                out.as_deref(),
+            ));
+        }
+        Some(Command::Path { from, to }) => {
+            process::exit(run_path(&dbs, from, to, cli.output));
+        }
+        Some(Command::Tui) => {
+            if let Err(err) = tui::run(&dbs, &unique_counts, cli.as_of()) {
+                error!("Terminal UI failed: {}", err);
+                eprintln!("{}", format!("Terminal UI failed: {}", err).red().bold());
+                process::exit(exit_code::UI_FAILED);
+            }
+            process::exit(exit_code::FOUND);
+        }
+        // Handled before loading
+        Some(Command::Date { .. }) => unreachable!(),
+        None => {}
+    }
 
     // Display counts of unique items
     println!("\n{}", "Counts of unique items:".cyan().bold());
-    println!(
-        "Unique Drive Manufacturers: {}",
-        manufacturer_counts_vec.len()
-    );
-    println!("Unique Builders: {}", built_by_counts_vec.len());
-    println!("Unique Enclosure S/N's: {}", unique_enclosure_sns.len());
+    println!("Unique Drive Manufacturers: {}", unique_counts.manufacturers);
+    println!("Unique Builders: {}", unique_counts.builders);
+    println!("Unique Enclosure S/N's: {}", unique_counts.enclosures);
 
     // Display top 15 Manufacturers and Builders side by side
     println!(
@@ -518,7 +1459,8 @@ fn main() {
         let manufacturer_str = if i < top_manufacturers.len() {
             let (manufacturer, count) = &top_manufacturers[i];
             let parent = manufacturer_to_parent.get(manufacturer).unwrap();
-            let color = parent_colors
+            let color = palette
+                .manufacturer_colors
                 .get(parent)
                 .cloned()
                 .unwrap_or(Color::White);
@@ -530,7 +1472,8 @@ fn main() {
         // Builder
         let builder_str = if i < top_built_bys.len() {
             let (built_by, count) = &top_built_bys[i];
-            let color = built_by_colors
+            let color = palette
+                .built_by_colors
                 .get(built_by)
                 .cloned()
                 .unwrap_or(Color::White);
@@ -549,11 +1492,37 @@ fn main() {
             .blue()
     );
 
+    let mut editor = match create_editor(&dbs) {
+        Ok(editor) => editor,
+        Err(err) => {
+            error!("Failed to start line editor: {}", err);
+            eprintln!("{}", format!("Failed to start line editor: {}", err).red().bold());
+            process::exit(exit_code::UI_FAILED);
+        }
+    };
+    let history_path = cli.history_file.clone().or_else(default_history_path);
+    if let Some(path) = &history_path {
+        // A missing history file just means a first run
+        if let Err(err) = editor.load_history(path) {
+            debug!("No history loaded from {}: {}", path.display(), err);
+        }
+    }
+
+    let mut history = SessionHistory::default();
+    let mut pager: Option<Pager> = None;
     println!("{}", "Please enter search criteria:".cyan().bold());
     loop {
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
+        let line = match editor.readline("") {
+            Ok(line) => line,
+            // Ctrl-C clears the line; Ctrl-D quits
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                error!("Error reading input: {}", err);
+                break;
+            }
+        };
+        let input = line.trim();
         debug!("User input: {}", input);
 
         if input.eq_ignore_ascii_case("Q") {
@@ -562,140 +1531,90 @@ fn main() {
             break;
         }
 
-        if input.starts_with("#") {
-            println!("{}", "Previous results functionality is not implemented in the new version.".yellow());
-        } else if input.starts_with("$") {
-            parse_and_print_date(&input[1..]);
-        } else {
-            info!("Searching all databases for: {}", input);
-            let mut found = false;
-
-            let mut printed_assemblies = HashSet::new();
-            let mut printed_drives = HashSet::new();
-            let mut printed_tickets = HashSet::new();
-
-            let assembly_results = search_in_assemblies(&assemblers, input);
-            if assembly_results.len() > 25 {
-                println!(
-                    "{}",
-                    format!(
-                        "Too many results found in assemblies ({}). Please refine your search.",
-                        assembly_results.len()
-                    )
-                    .red()
-                    .bold()
-                );
-                continue;
-            }
-
-            let drive_results = search_in_drives(&drive, input);
-            if drive_results.len() > 25 {
-                println!(
-                    "{}",
-                    format!(
-                        "Too many results found in drives ({}). Please refine your search.",
-                        drive_results.len()
-                    )
-                    .red()
-                    .bold()
-                );
-                continue;
-            }
-
-            let zendesk_results = search_in_zendesk(&zendesk_ticket, input);
-            if zendesk_results.len() > 25 {
-                println!(
-                    "{}",
-                    format!(
-                        "Too many results found in Zendesk tickets ({}). Please refine your search.",
-                        zendesk_results.len()
-                    )
-                    .red()
-                    .bold()
-                );
-                continue;
+        let page_command = input.to_lowercase();
+        if let (Some(pager), "n" | "p" | "all") = (pager.as_mut(), page_command.as_str()) {
+            match page_command.as_str() {
+                "n" if !pager.next_page() => {
+                    println!("{}", "Already on the last page.".yellow());
+                }
+                "p" if !pager.previous_page() => {
+                    println!("{}", "Already on the first page.".yellow());
+                }
+                "all" => pager.show_all(&palette, cli.output),
+                _ => pager.show(&palette, cli.output),
             }
-
-            // Now process the results if none exceed 25
-            if !assembly_results.is_empty() {
-                found = true;
-                for assembly in assembly_results {
-                    if !printed_assemblies.contains(&assembly.serial_number) {
-                        print_assembly(assembly, 0, &built_by_colors);
-                        printed_assemblies.insert(assembly.serial_number.clone());
-
-                        // Find related drives
-                        let related_drives: Vec<&Drive> = drive
-                            .drive
-                            .iter()
-                            .filter(|d| d.enclosure_sn == assembly.serial_number)
-                            .collect();
-
-                        for drive in related_drives {
-                            if !printed_drives.contains(&drive.drive_sn) {
-                                print_drive(drive, 1, &parent_colors);
-                                printed_drives.insert(drive.drive_sn.clone());
-                            }
+        } else if let Some(recall) = input.strip_prefix('#') {
+            match Recall::parse(recall) {
+                Ok(Recall::List) => print_history(&history),
+                Ok(Recall::Set(number)) => match history.get(number) {
+                    Some(entry) => {
+                        println!(
+                            "{}",
+                            format!("Result set #{}: {}", number, entry.query).cyan().bold()
+                        );
+                        let recalled = Pager::new(number, entry.results.clone(), cli.page_size);
+                        recalled.show(&palette, cli.output);
+                        pager = Some(recalled);
+                    }
+                    None => println!("{}", format!("No result set #{}.", number).red().bold()),
+                },
+                Ok(Recall::Record(number, record)) => {
+                    match history.get(number) {
+                        Some(entry) if record <= entry.results.len() => {
+                            let found = entry.results.page(record - 1, 1);
+                            show_results(&found, &palette, cli.output, Some(number));
                         }
-
-                        // Find related tickets
-                        let related_tickets: Vec<&ZendeskTicket> = zendesk_ticket
-                            .zendesk_ticket
-                            .iter()
-                            .filter(|t| t.serial == assembly.serial_number)
-                            .collect();
-
-                        for ticket in related_tickets {
-                            if !printed_tickets.contains(&ticket.rma) {
-                                print_zendesk_ticket(ticket, 1); // Adjusted indentation
-                                printed_tickets.insert(ticket.rma);
-                            }
+                        Some(entry) => println!(
+                            "{}",
+                            format!(
+                                "Result set #{} has only {} results.",
+                                number,
+                                entry.results.len()
+                            )
+                            .red()
+                            .bold()
+                        ),
+                        None => {
+                            println!("{}", format!("No result set #{}.", number).red().bold())
                         }
                     }
                 }
+                Err(message) => println!("{}", message.red().bold()),
             }
-
-            // Now process drives that haven't been printed yet
-            if !drive_results.is_empty() {
-                found = true;
-                for drive in drive_results {
-                    if !printed_drives.contains(&drive.drive_sn) {
-                        print_drive(drive, 0, &parent_colors);
-                        printed_drives.insert(drive.drive_sn.clone());
-
-                        // Find related tickets
-                        let related_tickets: Vec<&ZendeskTicket> = zendesk_ticket
-                            .zendesk_ticket
-                            .iter()
-                            .filter(|t| t.drive == drive.drive_sn)
-                            .collect();
-
-                        for ticket in related_tickets {
-                            if !printed_tickets.contains(&ticket.rma) {
-                                print_zendesk_ticket(ticket, 1); // Adjusted indentation
-                                printed_tickets.insert(ticket.rma);
-                            }
-                        }
+        } else if let Some(date_str) = input.strip_prefix('$') {
+            print_date_conversion(date_str, None, cli.output);
+        } else {
+            info!("Searching all databases for: {}", input);
+            match search_all(&dbs, input) {
+                Ok(results) if results.is_empty() => {
+                    println!("{}", SearchOutcome::NotFound.message().red().bold());
+                    if let Some(suggestion) = fuzzy::did_you_mean(&dbs, input) {
+                        info!("{}", suggestion);
+                        println!("{}", suggestion.yellow());
                     }
                 }
-            }
-
-            // Now process Zendesk tickets that haven't been printed yet
-            if !zendesk_results.is_empty() {
-                found = true;
-                for ticket in zendesk_results {
-                    if !printed_tickets.contains(&ticket.rma) {
-                        print_zendesk_ticket(ticket, 0);
-                        printed_tickets.insert(ticket.rma);
+                Ok(results) => {
+                    let number = history.push(input, results.clone());
+                    println!("{}", format!("Result set #{}", number).cyan().bold());
+                    if results.len() > cli.page_size {
+                        print_summary(&results);
                     }
+                    let new_pager = Pager::new(number, results, cli.page_size);
+                    new_pager.show(&palette, cli.output);
+                    pager = Some(new_pager);
+                }
+                Err(err) => {
+                    println!("{}", SearchOutcome::from(err).message().red().bold());
                 }
-            }
-
-            if !found {
-                println!("{}", "No matching results found.".red().bold());
             }
         }
 
         println!("\n{}", "Please enter search criteria:".cyan().bold());
     }
+
+    if let Some(path) = &history_path {
+        if let Err(err) = editor.save_history(path) {
+            warn!("Failed to save history to {}: {}", path.display(), err);
+        }
+    }
 }
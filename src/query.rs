@@ -0,0 +1,958 @@
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::dates::{epoch_millis_to_date, DateRange};
+use crate::fuzzy;
+use crate::index::{intersect, union, TableIndex};
+use crate::{Assembly, Drive, ZendeskTicket};
+
+// Record fields that a search term can be restricted to with `name:value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Serial,
+    SalesOrder,
+    Description,
+    BuiltBy,
+    Assembler,
+    Built,
+    Enclosure,
+    Drive,
+    Manufacturer,
+    Model,
+    PartNumber,
+    Rma,
+    Diagnosis,
+    OldDiagnosis,
+    NewDiagnosis,
+    DiagnosisDate,
+}
+
+const FIELD_NAMES: &[(&str, Field)] = &[
+    ("sn", Field::Serial),
+    ("serial", Field::Serial),
+    ("so", Field::SalesOrder),
+    ("desc", Field::Description),
+    ("builtby", Field::BuiltBy),
+    ("assembler", Field::Assembler),
+    ("built", Field::Built),
+    ("enc", Field::Enclosure),
+    ("drive", Field::Drive),
+    ("mfr", Field::Manufacturer),
+    ("model", Field::Model),
+    ("pn", Field::PartNumber),
+    ("rma", Field::Rma),
+    ("diag", Field::Diagnosis),
+    ("olddiag", Field::OldDiagnosis),
+    ("newdiag", Field::NewDiagnosis),
+    ("diagdate", Field::DiagnosisDate),
+];
+
+impl Field {
+    pub const ALL: &'static [Field] = &[
+        Field::Serial,
+        Field::SalesOrder,
+        Field::Description,
+        Field::BuiltBy,
+        Field::Assembler,
+        Field::Built,
+        Field::Enclosure,
+        Field::Drive,
+        Field::Manufacturer,
+        Field::Model,
+        Field::PartNumber,
+        Field::Rma,
+        Field::Diagnosis,
+        Field::OldDiagnosis,
+        Field::NewDiagnosis,
+        Field::DiagnosisDate,
+    ];
+
+    fn from_name(name: &str) -> Option<Field> {
+        FIELD_NAMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, field)| *field)
+    }
+}
+
+// A record that can be matched by a query.
+pub trait Searchable {
+    // Fields searched by a term without a field name.
+    const DEFAULT_FIELDS: &'static [Field];
+
+    // Uppercased values of `field` for this record, or None if the record has
+    // no such field.
+    fn field_values(&self, field: Field) -> Option<Vec<Cow<'_, str>>>;
+
+    // Value of a date `field`, or None if the record has no such date.
+    fn field_date(&self, _field: Field) -> Option<NaiveDate> {
+        None
+    }
+}
+
+impl Searchable for Assembly {
+    const DEFAULT_FIELDS: &'static [Field] =
+        &[Field::Serial, Field::SalesOrder, Field::Description];
+
+    fn field_values(&self, field: Field) -> Option<Vec<Cow<'_, str>>> {
+        let value = match field {
+            Field::Serial => &self.serial_number,
+            Field::SalesOrder => &self.sales_order,
+            Field::Description => &self.description,
+            Field::BuiltBy => &self.built_by,
+            _ => return None,
+        };
+        Some(vec![Cow::from(value.to_uppercase())])
+    }
+
+    fn field_date(&self, field: Field) -> Option<NaiveDate> {
+        match field {
+            Field::Built if self.built_date != 0 => epoch_millis_to_date(self.built_date),
+            _ => None,
+        }
+    }
+}
+
+impl Searchable for Drive {
+    const DEFAULT_FIELDS: &'static [Field] = &[
+        Field::Enclosure,
+        Field::Drive,
+        Field::Manufacturer,
+        Field::Model,
+        Field::PartNumber,
+    ];
+
+    fn field_values(&self, field: Field) -> Option<Vec<Cow<'_, str>>> {
+        let values = match field {
+            Field::Serial => vec![&self.enclosure_sn, &self.drive_sn],
+            Field::Enclosure => vec![&self.enclosure_sn],
+            Field::Drive => vec![&self.drive_sn],
+            Field::Manufacturer => vec![&self.drive_manufacturer],
+            Field::Model => vec![&self.model],
+            Field::PartNumber => vec![&self.part_number],
+            _ => return None,
+        };
+        Some(
+            values
+                .into_iter()
+                .map(|value| Cow::from(value.to_uppercase()))
+                .collect(),
+        )
+    }
+}
+
+impl Searchable for ZendeskTicket {
+    const DEFAULT_FIELDS: &'static [Field] = &[
+        Field::Rma,
+        Field::Serial,
+        Field::Diagnosis,
+        Field::Assembler,
+    ];
+
+    fn field_values(&self, field: Field) -> Option<Vec<Cow<'_, str>>> {
+        let values = match field {
+            Field::Rma => return Some(vec![Cow::from(self.rma.to_string())]),
+            Field::Serial => vec![&self.serial, &self.drive],
+            Field::Drive => vec![&self.drive],
+            Field::Assembler => vec![&self.assembler],
+            Field::Diagnosis => vec![&self.old_diagnosis, &self.new_diagnosis],
+            Field::OldDiagnosis => vec![&self.old_diagnosis],
+            Field::NewDiagnosis => vec![&self.new_diagnosis],
+            _ => return None,
+        };
+        Some(
+            values
+                .into_iter()
+                .map(|value| Cow::from(value.to_uppercase()))
+                .collect(),
+        )
+    }
+
+    fn field_date(&self, field: Field) -> Option<NaiveDate> {
+        match field {
+            Field::DiagnosisDate => self.diagnosis_date,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryError {
+    pub message: String,
+    // Byte offset of the offending text in the query
+    pub position: usize,
+    pub input: String,
+}
+
+impl QueryError {
+    fn new(message: String, position: usize) -> QueryError {
+        QueryError {
+            message,
+            position,
+            input: String::new(),
+        }
+    }
+
+    // Characters before the offending text. Counting them rather than slicing
+    // keeps a position inside a character from panicking.
+    fn column(&self) -> usize {
+        self.input
+            .char_indices()
+            .take_while(|(offset, _)| *offset < self.position)
+            .count()
+    }
+
+    // The query with a caret under the offending text.
+    pub fn pointer(&self) -> String {
+        format!("    {}\n    {}^", self.input, " ".repeat(self.column()))
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.column() + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    // Case-insensitive substring, stored uppercased
+    Contains(String),
+    // `*` and `?` wildcards matched against the whole value; `literal` is the
+    // longest run of plain text in it, for index lookups
+    Glob { regex: Regex, literal: String },
+    // `/regex/`, found anywhere in the value, ignoring case
+    Regex(Regex),
+    // Dates within a range, for date fields
+    Dates(DateRange),
+    // `~text`: whole values within a few typos of the text, stored uppercased
+    Fuzzy(String),
+}
+
+impl Pattern {
+    // Matches an uppercased value.
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Pattern::Contains(text) => value.contains(text.as_str()),
+            Pattern::Glob { regex, .. } | Pattern::Regex(regex) => regex.is_match(value),
+            Pattern::Dates(_) => false,
+            Pattern::Fuzzy(text) => fuzzy::is_close(value, text),
+        }
+    }
+
+    // Text that every matching value contains, for index lookups.
+    fn required_text(&self) -> Option<&str> {
+        match self {
+            Pattern::Contains(text) => Some(text),
+            Pattern::Glob { literal, .. } => Some(literal),
+            Pattern::Regex(_) | Pattern::Dates(_) | Pattern::Fuzzy(_) => None,
+        }
+    }
+}
+
+// Translates a glob into an anchored regex: `*` is any text and `?` any one
+// character.
+fn glob_pattern(upper: &str) -> Result<Pattern, String> {
+    let regex = glob_regex(upper).map_err(|err| format!("Invalid wildcard pattern: {}", err))?;
+    // The longest run without wildcards, which every match must contain
+    let mut literal = "";
+    for run in upper.split(['*', '?']) {
+        if run.len() > literal.len() {
+            literal = run;
+        }
+    }
+    Ok(Pattern::Glob {
+        regex,
+        literal: literal.to_string(),
+    })
+}
+
+// A case-insensitive regex matching the whole of a value against a glob.
+pub fn glob_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut source = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => source.push_str(".*"),
+            '?' => source.push('.'),
+            _ => source.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    source.push('$');
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .dot_matches_new_line(true)
+        .build()
+}
+
+// Compiles a `/regex/` term, pointing errors at the offending part of the
+// expression, which starts at `position` in the query.
+fn regex_pattern(source: &str, position: usize) -> Result<Pattern, QueryError> {
+    let invalid = |message: String, offset: usize| {
+        // Every `/` in the source was written `\/` in the query
+        let escapes = source.get(..offset).map_or(0, |before| before.matches('/').count());
+        QueryError::new(
+            format!("Invalid regular expression: {}", message),
+            position + offset + escapes,
+        )
+    };
+    if let Err(err) = regex_syntax::ParserBuilder::new()
+        .case_insensitive(true)
+        .build()
+        .parse(source)
+    {
+        return Err(match &err {
+            regex_syntax::Error::Parse(err) => invalid(err.kind().to_string(), err.span().start.offset),
+            regex_syntax::Error::Translate(err) => {
+                invalid(err.kind().to_string(), err.span().start.offset)
+            }
+            _ => invalid(err.to_string(), 0),
+        });
+    }
+    RegexBuilder::new(source)
+        .case_insensitive(true)
+        .build()
+        .map(Pattern::Regex)
+        .map_err(|err| invalid(err.to_string(), 0))
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    field: Option<Field>,
+    pattern: Pattern,
+}
+
+impl Term {
+    fn phrase(text: &str) -> Term {
+        Term {
+            field: None,
+            pattern: Pattern::Contains(text.to_uppercase()),
+        }
+    }
+
+    fn from_word(word: Word) -> Result<Term, QueryError> {
+        let field = match &word.field {
+            None => None,
+            Some((field, name)) => {
+                if word.value.is_empty() && !word.regex {
+                    return Err(QueryError::new(
+                        format!("Missing value after `{}:`", name),
+                        word.position,
+                    ));
+                }
+                Some(*field)
+            }
+        };
+        Ok(Term {
+            field,
+            pattern: word_pattern(field, &word)?,
+        })
+    }
+
+    fn matches<R: Searchable>(&self, record: &R) -> bool {
+        if let (Some(field), Pattern::Dates(range)) = (self.field, &self.pattern) {
+            return record
+                .field_date(field)
+                .is_some_and(|date| range.contains(date));
+        }
+        match self.field {
+            Some(field) => match record.field_values(field) {
+                Some(values) => values.iter().any(|value| self.pattern.matches(value)),
+                None => false,
+            },
+            None => R::DEFAULT_FIELDS.iter().any(|field| {
+                record
+                    .field_values(*field)
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|value| self.pattern.matches(value))
+            }),
+        }
+    }
+
+    fn candidates<R: Searchable>(&self, index: &TableIndex) -> Option<Vec<u32>> {
+        let text = self.pattern.required_text()?;
+        match self.field {
+            Some(field) => index.lookup(&[field], text),
+            None => index.lookup(R::DEFAULT_FIELDS, text),
+        }
+    }
+}
+
+// A parsed boolean expression over search terms.
+#[derive(Debug, Clone)]
+enum Expr {
+    Term(Term),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    fn matches<R: Searchable>(&self, record: &R) -> bool {
+        match self {
+            Expr::Term(term) => term.matches(record),
+            Expr::And(left, right) => left.matches(record) && right.matches(record),
+            Expr::Or(left, right) => left.matches(record) || right.matches(record),
+            Expr::Not(inner) => !inner.matches(record),
+        }
+    }
+
+    // Positions of the records that can match, or None if any record can.
+    fn candidates<R: Searchable>(&self, index: &TableIndex) -> Option<Vec<u32>> {
+        match self {
+            Expr::Term(term) => term.candidates::<R>(index),
+            Expr::And(left, right) => match (
+                left.candidates::<R>(index),
+                right.candidates::<R>(index),
+            ) {
+                (Some(left), Some(right)) => Some(intersect(&left, &right)),
+                (Some(ids), None) | (None, Some(ids)) => Some(ids),
+                (None, None) => None,
+            },
+            Expr::Or(left, right) => {
+                let left = left.candidates::<R>(index)?;
+                let right = right.candidates::<R>(index)?;
+                Some(union(&left, &right))
+            }
+            Expr::Not(_) => None,
+        }
+    }
+
+    fn serial_texts(&self, texts: &mut Vec<String>) {
+        match self {
+            Expr::Term(Term { field, pattern }) => {
+                let serial_field = matches!(
+                    field,
+                    None | Some(Field::Serial) | Some(Field::Enclosure) | Some(Field::Drive)
+                );
+                if let (true, Pattern::Contains(text) | Pattern::Fuzzy(text)) =
+                    (serial_field, pattern)
+                {
+                    texts.push(text.clone());
+                }
+            }
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.serial_texts(texts);
+                right.serial_texts(texts);
+            }
+            Expr::Not(_) => {}
+        }
+    }
+}
+
+// A parsed search. Terms combine with AND, OR and NOT (upper case) and
+// parentheses; terms next to each other must both match. Bare words are joined
+// into one phrase, so `Atom 2000` searches for the text "Atom 2000" as before.
+// An empty query matches everything.
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Option<Expr>,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        Query::parse_expr(input).map_err(|err| QueryError {
+            input: input.to_string(),
+            ..err
+        })
+    }
+
+    fn parse_expr(input: &str) -> Result<Query, QueryError> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            next: 0,
+            end: input.len(),
+        };
+        if parser.tokens.is_empty() {
+            return Ok(Query { expr: None });
+        }
+        let expr = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(Query { expr: Some(expr) }),
+            Some(Token::Close(position)) => {
+                Err(QueryError::new("Unmatched `)`".to_string(), *position))
+            }
+            Some(token) => Err(QueryError::new(
+                format!("Unexpected {}", token.describe()),
+                token.position(),
+            )),
+        }
+    }
+
+    pub fn matches<R: Searchable>(&self, record: &R) -> bool {
+        match &self.expr {
+            Some(expr) => expr.matches(record),
+            None => true,
+        }
+    }
+
+    // Narrows a search to the records that can match, using the table's
+    // trigram index; None means every record has to be checked.
+    pub fn candidates<R: Searchable>(&self, index: &TableIndex) -> Option<Vec<u32>> {
+        self.expr.as_ref()?.candidates::<R>(index)
+    }
+
+    // Uppercased text of the terms that may be serial numbers, for "did you
+    // mean" suggestions. Terms under NOT are left out.
+    pub fn serial_texts(&self) -> Vec<String> {
+        let mut texts = Vec::new();
+        if let Some(expr) = &self.expr {
+            expr.serial_texts(&mut texts);
+        }
+        texts
+    }
+}
+
+// Recursive descent parser. Precedence from loosest to tightest: OR, AND
+// (explicit or implied by adjacency), NOT.
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.parse_and()?;
+        while let Some(Token::Or(_)) = self.peek() {
+            self.advance();
+            let right = self.parse_and()?;
+            expr = Expr::Or(Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.parse_not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And(_)) => {
+                    self.advance();
+                }
+                Some(Token::Word(_)) | Some(Token::Not(_)) | Some(Token::Open(_)) => {}
+                _ => break,
+            }
+            let right = self.parse_not()?;
+            expr = Expr::And(Box::new(expr), Box::new(right));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QueryError> {
+        if let Some(Token::Not(_)) = self.peek() {
+            self.advance();
+            let inner = self.parse_not()?;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let previous = self.next.checked_sub(1).and_then(|i| self.tokens.get(i));
+        let expected = match previous {
+            Some(token) => format!("Expected a search term after {}", token.describe()),
+            None => "Expected a search term".to_string(),
+        };
+        match self.advance() {
+            Some(Token::Open(open)) => {
+                if let Some(Token::Close(close)) = self.peek() {
+                    return Err(QueryError::new("Empty parentheses".to_string(), *close));
+                }
+                let expr = self.parse_or()?;
+                match self.advance() {
+                    Some(Token::Close(_)) => Ok(expr),
+                    _ => Err(QueryError::new(
+                        "Missing `)` to close this `(`".to_string(),
+                        open,
+                    )),
+                }
+            }
+            Some(Token::Word(word)) if word.is_bare() => {
+                // Join following bare words into one phrase
+                let mut text = word.value;
+                while let Some(Token::Word(next)) = self.peek() {
+                    if !next.is_bare() {
+                        break;
+                    }
+                    text.push(' ');
+                    text.push_str(&next.value);
+                    self.advance();
+                }
+                Ok(Expr::Term(Term::phrase(&text)))
+            }
+            Some(Token::Word(word)) => Ok(Expr::Term(Term::from_word(word)?)),
+            Some(token) => Err(QueryError::new(
+                format!("{}, found {}", expected, token.describe()),
+                token.position(),
+            )),
+            None => Err(QueryError::new(expected, self.end)),
+        }
+    }
+}
+
+fn word_pattern(field: Option<Field>, word: &Word) -> Result<Pattern, QueryError> {
+    if word.fuzzy && word.value.is_empty() {
+        return Err(QueryError::new(
+            "Missing value after `~`".to_string(),
+            word.position,
+        ));
+    }
+    if word.regex && word.value.is_empty() {
+        return Err(QueryError::new(
+            "Empty regular expression".to_string(),
+            word.position,
+        ));
+    }
+    if matches!(field, Some(Field::Built | Field::DiagnosisDate)) {
+        if word.fuzzy || word.regex {
+            return Err(QueryError::new(
+                "`~` and /regex/ can't be used with built: and diagdate: dates".to_string(),
+                word.position,
+            ));
+        }
+        let today = chrono::Utc::now().naive_utc().date();
+        return DateRange::parse(&word.value, today)
+            .map(Pattern::Dates)
+            .map_err(|message| QueryError::new(message, word.position));
+    }
+    if word.regex {
+        return regex_pattern(&word.value, word.position);
+    }
+    let upper = word.value.to_uppercase();
+    if word.fuzzy {
+        Ok(Pattern::Fuzzy(upper))
+    } else if word.is_glob() {
+        glob_pattern(&upper).map_err(|message| QueryError::new(message, word.position))
+    } else {
+        Ok(Pattern::Contains(upper))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Word {
+    // Field and the name it was written as, for `name:value` words
+    field: Option<(Field, String)>,
+    value: String,
+    quoted: bool,
+    // Value started with `~`
+    fuzzy: bool,
+    // Value was written as `/regex/`
+    regex: bool,
+    // Byte offsets of the whole word and of its value
+    start: usize,
+    position: usize,
+}
+
+impl Word {
+    fn is_bare(&self) -> bool {
+        self.field.is_none() && !self.quoted && !self.fuzzy && !self.regex && !self.is_glob()
+    }
+
+    // Unquoted text with `*` or `?` wildcards.
+    fn is_glob(&self) -> bool {
+        !self.quoted && !self.regex && self.value.contains(['*', '?'])
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Word(Word),
+    And(usize),
+    Or(usize),
+    Not(usize),
+    Open(usize),
+    Close(usize),
+}
+
+impl Token {
+    fn position(&self) -> usize {
+        match self {
+            Token::Word(word) => word.start,
+            Token::And(position)
+            | Token::Or(position)
+            | Token::Not(position)
+            | Token::Open(position)
+            | Token::Close(position) => *position,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("`{}`", word.value),
+            Token::And(_) => "`AND`".to_string(),
+            Token::Or(_) => "`OR`".to_string(),
+            Token::Not(_) => "`NOT`".to_string(),
+            Token::Open(_) => "`(`".to_string(),
+            Token::Close(_) => "`)`".to_string(),
+        }
+    }
+}
+
+// Splits the input into words, operators and parentheses. Words end at
+// whitespace or a parenthesis outside double quotes, and a leading `name:`
+// that names a field is separated from the value.
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' {
+                Token::Open(start)
+            } else {
+                Token::Close(start)
+            });
+            continue;
+        }
+
+        let mut text = String::new();
+        let mut quoted = false;
+        let mut fuzzy = false;
+        let mut regex = false;
+        let mut field = None;
+        let mut value_start = start;
+        while let Some(&(pos, c)) = chars.peek() {
+            if c.is_whitespace() || c == '(' || c == ')' {
+                break;
+            }
+            chars.next();
+            if c == '"' {
+                quoted = true;
+                let mut closed = false;
+                for (_, c) in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    text.push(c);
+                }
+                if !closed {
+                    return Err(QueryError::new("Unterminated quote".to_string(), pos));
+                }
+            } else if c == '/' && text.is_empty() && !quoted && !fuzzy && !regex {
+                // A regex runs to the next unescaped `/`, spaces and all
+                regex = true;
+                value_start = pos + 1;
+                let mut closed = false;
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '/' => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, '/')) => text.push('/'),
+                            Some((_, next)) => {
+                                text.push('\\');
+                                text.push(next);
+                            }
+                            None => text.push('\\'),
+                        },
+                        _ => text.push(c),
+                    }
+                }
+                if !closed {
+                    return Err(QueryError::new(
+                        "Unterminated regular expression".to_string(),
+                        pos,
+                    ));
+                }
+                if let Some(&(next, c)) = chars.peek() {
+                    if !c.is_whitespace() && c != '(' && c != ')' {
+                        return Err(QueryError::new(
+                            "Unexpected text after regular expression".to_string(),
+                            next,
+                        ));
+                    }
+                }
+            } else if c == '~' && text.is_empty() && !quoted && !fuzzy && !regex {
+                fuzzy = true;
+                value_start = pos + 1;
+            } else if c == ':' && field.is_none() && !quoted && !fuzzy && Field::from_name(&text).is_some() {
+                // Other names, as in `Error:` or URLs, stay part of the text
+                let name = std::mem::take(&mut text);
+                field = Field::from_name(&name).map(|known| (known, name));
+                value_start = pos + 1;
+            } else {
+                text.push(c);
+            }
+        }
+
+        let word = Word {
+            field,
+            value: text,
+            quoted,
+            fuzzy,
+            regex,
+            start,
+            position: value_start,
+        };
+        tokens.push(match word.value.as_str() {
+            "AND" if word.is_bare() => Token::And(start),
+            "OR" if word.is_bare() => Token::Or(start),
+            "NOT" if word.is_bare() => Token::Not(start),
+            _ => Token::Word(word),
+        });
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Assembly;
+
+    fn assembly(sales_order: &str, description: &str) -> Assembly {
+        Assembly {
+            serial_number: "1BBK1234567890".to_string(),
+            sales_order: sales_order.to_string(),
+            description: description.to_string(),
+            ..Assembly::default()
+        }
+    }
+
+    fn matches(query: &str, record: &Assembly) -> bool {
+        Query::parse(query).unwrap().matches(record)
+    }
+
+    fn error_at(query: &str) -> (String, usize) {
+        let err = Query::parse(query).unwrap_err();
+        (err.message, err.position)
+    }
+
+    #[test]
+    fn tokenize_separates_known_field_names() {
+        let tokens = tokenize(r#"so:123 desc:"Atom 2000" Error:42 http://x"#).unwrap();
+        let words: Vec<(Option<Field>, &str)> = tokens
+            .iter()
+            .map(|token| match token {
+                Token::Word(word) => (
+                    word.field.as_ref().map(|(field, _)| *field),
+                    word.value.as_str(),
+                ),
+                _ => panic!("unexpected operator"),
+            })
+            .collect();
+        assert_eq!(
+            words,
+            [
+                (Some(Field::SalesOrder), "123"),
+                (Some(Field::Description), "Atom 2000"),
+                (None, "Error:42"),
+                (None, "http://x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_reads_operators_only_when_bare() {
+        let tokens = tokenize(r#"a AND "OR" NOT (b)"#).unwrap();
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Token::Word(_),
+                Token::And(2),
+                Token::Word(_),
+                Token::Not(11),
+                Token::Open(15),
+                Token::Word(_),
+                Token::Close(17),
+            ]
+        ));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let query = "so:111 OR so:222 AND desc:black";
+        assert!(matches(query, &assembly("111", "White")));
+        assert!(matches(query, &assembly("222", "Black")));
+        assert!(!matches(query, &assembly("222", "White")));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        let query = "NOT so:111 AND desc:black";
+        assert!(matches(query, &assembly("222", "Black")));
+        assert!(!matches(query, &assembly("111", "Black")));
+        assert!(!matches(query, &assembly("222", "White")));
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        let query = "(so:111 OR so:222) desc:black";
+        assert!(matches(query, &assembly("111", "Black")));
+        assert!(!matches(query, &assembly("111", "White")));
+    }
+
+    #[test]
+    fn adjacent_terms_are_anded() {
+        assert!(matches("so:111 desc:black", &assembly("111", "Black")));
+        assert!(!matches("so:111 desc:black", &assembly("111", "White")));
+    }
+
+    #[test]
+    fn bare_words_form_one_phrase() {
+        assert!(matches("Atom 2000", &assembly("1", "Atom 2000, Black")));
+        assert!(!matches("Atom Black", &assembly("1", "Atom 2000, Black")));
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        assert!(matches("   ", &assembly("1", "")));
+    }
+
+    #[test]
+    fn regex_errors_skip_escaped_slashes() {
+        let err = Query::parse(r"pn:/a\/\/b(/").unwrap_err();
+        assert_eq!(err.position, 10);
+        assert!(err.to_string().ends_with("(at column 11)"), "{}", err);
+        assert!(err.pointer().ends_with(&format!("{}^", " ".repeat(14))));
+    }
+
+    #[test]
+    fn errors_after_non_ascii_text_count_characters() {
+        let err = Query::parse(r"/\/é[/").unwrap_err();
+        assert_eq!(err.position, 5);
+        assert!(err.to_string().ends_with("(at column 5)"), "{}", err);
+        // A position inside `é` doesn't panic
+        let inside = QueryError {
+            position: 4,
+            ..err
+        };
+        assert!(inside.to_string().ends_with("(at column 5)"), "{}", inside);
+        assert!(inside.pointer().ends_with(&format!("{}^", " ".repeat(8))));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_text() {
+        assert_eq!(error_at("(so:1").1, 0);
+        assert_eq!(error_at("so:1 )"), ("Unmatched `)`".to_string(), 5));
+        assert_eq!(
+            error_at("so:1 AND"),
+            ("Expected a search term after `AND`".to_string(), 8)
+        );
+        assert_eq!(error_at("so:"), ("Missing value after `so:`".to_string(), 3));
+        assert_eq!(error_at(r#"a "b"#), ("Unterminated quote".to_string(), 2));
+        assert_eq!(error_at("a ()").1, 3);
+    }
+}
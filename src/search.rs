@@ -0,0 +1,411 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::graph::Node;
+use crate::query::{Query, QueryError};
+use crate::{Assembly, Databases, Drive, ZendeskTicket};
+
+// Positions of the matching records in each database.
+pub fn search_in_assemblies(dbs: &Databases, query: &Query) -> Vec<usize> {
+    dbs.index.assemblies.search(&dbs.assemblies.asm, query)
+}
+
+pub fn search_in_drives(dbs: &Databases, query: &Query) -> Vec<usize> {
+    dbs.index.drives.search(&dbs.drives.drive, query)
+}
+
+pub fn search_in_zendesk(dbs: &Databases, query: &Query) -> Vec<usize> {
+    dbs.index.tickets.search(&dbs.zendesk.zendesk_ticket, query)
+}
+
+// Search results grouped the way they are displayed: matched assemblies with
+// their drives and tickets, then drives and tickets not already shown.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SearchResults<'a> {
+    // Position of the first result within the full result set
+    #[serde(skip)]
+    pub offset: usize,
+    #[serde(rename = "Counts")]
+    pub counts: MatchCounts,
+    #[serde(rename = "Assemblies")]
+    pub assemblies: Vec<AssemblyMatch<'a>>,
+    #[serde(rename = "Drives")]
+    pub drives: Vec<DriveMatch<'a>>,
+    #[serde(rename = "Tickets")]
+    pub tickets: Vec<TicketMatch<'a>>,
+}
+
+// Number of records that matched in each database, before grouping.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct MatchCounts {
+    #[serde(rename = "Assemblies")]
+    pub assemblies: usize,
+    #[serde(rename = "Drives")]
+    pub drives: usize,
+    #[serde(rename = "Tickets")]
+    pub tickets: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AssemblyMatch<'a> {
+    #[serde(flatten)]
+    pub assembly: &'a Assembly,
+    #[serde(rename = "Drives")]
+    pub drives: Vec<&'a Drive>,
+    #[serde(rename = "Tickets")]
+    pub tickets: Vec<&'a ZendeskTicket>,
+}
+
+// A matched drive with the assembly it is installed in, the other drives in
+// that enclosure, and the tickets filed against the drive or the enclosure.
+#[derive(Debug, Clone, Serialize)]
+pub struct DriveMatch<'a> {
+    #[serde(flatten)]
+    pub drive: &'a Drive,
+    #[serde(rename = "Enclosure")]
+    pub enclosure: Option<&'a Assembly>,
+    // Drives in the same enclosure that aren't results of their own
+    #[serde(rename = "Siblings")]
+    pub siblings: Vec<&'a Drive>,
+    #[serde(rename = "Tickets")]
+    pub tickets: Vec<&'a ZendeskTicket>,
+}
+
+// A matched ticket with the assembly and drive it was filed against, and the
+// other tickets for that drive or assembly. The ticket already has a `Drive`
+// column, hence `DriveRecord`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TicketMatch<'a> {
+    #[serde(flatten)]
+    pub ticket: &'a ZendeskTicket,
+    #[serde(rename = "Enclosure")]
+    pub assembly: Option<&'a Assembly>,
+    #[serde(rename = "DriveRecord")]
+    pub drive: Option<&'a Drive>,
+    #[serde(rename = "OtherTickets")]
+    pub tickets: Vec<&'a ZendeskTicket>,
+}
+
+impl<'a> SearchResults<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Number of top-level results: assemblies, then drives, then tickets.
+    pub fn len(&self) -> usize {
+        self.assemblies.len() + self.drives.len() + self.tickets.len()
+    }
+
+    // The top-level results from `offset`, at most `limit` of them, keeping
+    // the counts of the full result set.
+    pub fn page(&self, offset: usize, limit: usize) -> SearchResults<'a> {
+        let mut remaining = limit;
+        let mut skip = offset;
+        let mut take = |len: usize| {
+            let start = skip.min(len);
+            skip -= start;
+            let end = start + remaining.min(len - start);
+            remaining -= end - start;
+            start..end
+        };
+        SearchResults {
+            offset: self.offset + offset,
+            counts: self.counts,
+            assemblies: self.assemblies[take(self.assemblies.len())].to_vec(),
+            drives: self.drives[take(self.drives.len())].to_vec(),
+            tickets: self.tickets[take(self.tickets.len())].to_vec(),
+        }
+    }
+}
+
+// Parses the query and searches all three databases.
+pub fn search_all<'a>(dbs: &'a Databases, input: &str) -> Result<SearchResults<'a>, QueryError> {
+    let query = Query::parse(input)?;
+
+    let assembly_results = search_in_assemblies(dbs, &query);
+    let drive_results = search_in_drives(dbs, &query);
+    let zendesk_results = search_in_zendesk(dbs, &query);
+
+    Ok(group_results(
+        dbs,
+        assembly_results,
+        drive_results,
+        zendesk_results,
+    ))
+}
+
+// Attaches related drives and tickets to each matched assembly, and the
+// surrounding assembly, drives and tickets to each matched drive or ticket,
+// following the links in the graph. Every result appears at most once.
+fn group_results(
+    dbs: &Databases,
+    assembly_results: Vec<usize>,
+    drive_results: Vec<usize>,
+    zendesk_results: Vec<usize>,
+) -> SearchResults<'_> {
+    let graph = &dbs.graph;
+    let assembly = |i: usize| &dbs.assemblies.asm[i];
+    let drive = |i: usize| &dbs.drives.drive[i];
+    let ticket = |i: usize| &dbs.zendesk.zendesk_ticket[i];
+    let drives_of = |nodes: &[Node]| -> Vec<usize> {
+        nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Drive(i) => Some(*i),
+                _ => None,
+            })
+            .collect()
+    };
+    let tickets_of = |nodes: &[Node]| -> Vec<usize> {
+        nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Ticket(i) => Some(*i),
+                _ => None,
+            })
+            .collect()
+    };
+    let assemblies_of = |nodes: &[Node]| -> Vec<usize> {
+        nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Assembly(i) => Some(*i),
+                _ => None,
+            })
+            .collect()
+    };
+    let assembly_of = |nodes: &[Node]| assemblies_of(nodes).first().map(|&a| assembly(a));
+
+    let mut results = SearchResults {
+        counts: MatchCounts {
+            assemblies: assembly_results.len(),
+            drives: drive_results.len(),
+            tickets: zendesk_results.len(),
+        },
+        ..SearchResults::default()
+    };
+    let mut seen_assemblies = HashSet::new();
+    let mut seen_drives = HashSet::new();
+    let mut seen_tickets = HashSet::new();
+
+    for a in assembly_results {
+        let found = assembly(a);
+        if !seen_assemblies.insert(&found.serial_number) {
+            continue;
+        }
+        let children = graph.children(Node::Assembly(a));
+
+        let drives = drives_of(children)
+            .into_iter()
+            .map(drive)
+            .filter(|d| seen_drives.insert(&d.drive_sn))
+            .collect();
+
+        let tickets = tickets_of(children)
+            .into_iter()
+            .map(ticket)
+            .filter(|t| seen_tickets.insert(t.rma))
+            .collect();
+
+        results.assemblies.push(AssemblyMatch {
+            assembly: found,
+            drives,
+            tickets,
+        });
+    }
+
+    // Drives that haven't been shown under an assembly, with their enclosure
+    let matched_drives: HashSet<&String> = drive_results.iter().map(|&d| &drive(d).drive_sn).collect();
+    for d in drive_results {
+        let found = drive(d);
+        if !seen_drives.insert(&found.drive_sn) {
+            continue;
+        }
+        let node = Node::Drive(d);
+        let parents = graph.parents(node);
+
+        let siblings = graph
+            .siblings(node)
+            .filter_map(|sibling| match sibling {
+                Node::Drive(i) => Some(drive(i)),
+                _ => None,
+            })
+            .filter(|sibling| sibling.drive_sn != found.drive_sn && !matched_drives.contains(&sibling.drive_sn))
+            .collect();
+
+        // Tickets for the drive, then for its enclosure
+        let mut ticket_ids = tickets_of(graph.children(node));
+        for parent in parents {
+            ticket_ids.extend(tickets_of(graph.children(*parent)));
+        }
+        let tickets = ticket_ids
+            .into_iter()
+            .map(ticket)
+            .filter(|t| seen_tickets.insert(t.rma))
+            .collect();
+
+        results.drives.push(DriveMatch {
+            drive: found,
+            enclosure: assembly_of(parents),
+            siblings,
+            tickets,
+        });
+    }
+
+    // Zendesk tickets that haven't been shown yet
+    for t in zendesk_results {
+        let found = ticket(t);
+        if !seen_tickets.insert(found.rma) {
+            continue;
+        }
+        let parents = graph.parents(Node::Ticket(t));
+        let found_drive = drives_of(parents).first().copied();
+
+        // A ticket filed against only a drive reaches its enclosure through
+        // the drive
+        let enclosure = assemblies_of(parents).first().copied().or_else(|| {
+            let d = found_drive?;
+            assemblies_of(graph.parents(Node::Drive(d))).first().copied()
+        });
+
+        // Other tickets for the drive, then for the enclosure
+        let mut ticket_ids = Vec::new();
+        if let Some(d) = found_drive {
+            ticket_ids.extend(tickets_of(graph.children(Node::Drive(d))));
+        }
+        if let Some(a) = enclosure {
+            ticket_ids.extend(tickets_of(graph.children(Node::Assembly(a))));
+        }
+        let tickets = ticket_ids
+            .into_iter()
+            .map(ticket)
+            .filter(|t| seen_tickets.insert(t.rma))
+            .collect();
+
+        results.tickets.push(TicketMatch {
+            ticket: found,
+            assembly: enclosure.map(assembly),
+            drive: found_drive.map(drive),
+            tickets,
+        });
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Records {
+        assemblies: Vec<Assembly>,
+        drives: Vec<Drive>,
+        tickets: Vec<ZendeskTicket>,
+    }
+
+    fn records() -> Records {
+        Records {
+            assemblies: ["A1", "A2"]
+                .map(|serial| Assembly {
+                    serial_number: serial.to_string(),
+                    ..Assembly::default()
+                })
+                .to_vec(),
+            drives: ["D1", "D2"]
+                .map(|serial| Drive {
+                    drive_sn: serial.to_string(),
+                    ..Drive::default()
+                })
+                .to_vec(),
+            tickets: [1, 2]
+                .map(|rma| ZendeskTicket {
+                    rma,
+                    ..ZendeskTicket::default()
+                })
+                .to_vec(),
+        }
+    }
+
+    // Two of each kind of result, with counts that differ from the lengths.
+    fn results(records: &Records) -> SearchResults<'_> {
+        SearchResults {
+            offset: 0,
+            counts: MatchCounts {
+                assemblies: 2,
+                drives: 3,
+                tickets: 4,
+            },
+            assemblies: records
+                .assemblies
+                .iter()
+                .map(|assembly| AssemblyMatch {
+                    assembly,
+                    drives: Vec::new(),
+                    tickets: Vec::new(),
+                })
+                .collect(),
+            drives: records
+                .drives
+                .iter()
+                .map(|drive| DriveMatch {
+                    drive,
+                    enclosure: None,
+                    siblings: Vec::new(),
+                    tickets: Vec::new(),
+                })
+                .collect(),
+            tickets: records
+                .tickets
+                .iter()
+                .map(|ticket| TicketMatch {
+                    ticket,
+                    assembly: None,
+                    drive: None,
+                    tickets: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    // Labels of the results in display order.
+    fn labels(results: &SearchResults) -> Vec<String> {
+        results
+            .assemblies
+            .iter()
+            .map(|found| found.assembly.serial_number.clone())
+            .chain(results.drives.iter().map(|found| found.drive.drive_sn.clone()))
+            .chain(results.tickets.iter().map(|found| found.ticket.rma.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn page_spans_kinds_of_results() {
+        let records = records();
+        let all = results(&records);
+        assert_eq!(all.len(), 6);
+        let page = all.page(1, 3);
+        assert_eq!(labels(&page), ["A2", "D1", "D2"]);
+        assert_eq!(page.offset, 1);
+        assert_eq!(page.counts.drives, 3);
+        assert_eq!(page.counts.tickets, 4);
+        assert_eq!(labels(&all.page(3, 10)), ["D2", "1", "2"]);
+    }
+
+    #[test]
+    fn page_past_the_end_is_empty() {
+        let records = records();
+        let all = results(&records);
+        assert!(all.page(6, 5).is_empty());
+        assert!(all.page(100, 5).is_empty());
+        assert!(all.page(0, 0).is_empty());
+    }
+
+    #[test]
+    fn page_of_a_page_keeps_the_overall_offset() {
+        let records = records();
+        let all = results(&records);
+        let page = all.page(2, 3).page(1, 1);
+        assert_eq!(labels(&page), ["D2"]);
+        assert_eq!(page.offset, 3);
+    }
+}
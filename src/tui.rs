@@ -0,0 +1,348 @@
+use chrono::NaiveDate;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+
+use crate::dates::epoch_millis_to_date;
+use crate::fuzzy;
+use crate::search::{search_all, SearchResults};
+use crate::warranty::{Warranty, WarrantyRules};
+use crate::{Assembly, Databases, Drive, UniqueCounts, ZendeskTicket};
+
+// Runs the full-screen interface until the user quits.
+// Warranties are evaluated at `as_of`.
+pub fn run(dbs: &Databases, counts: &UniqueCounts, as_of: NaiveDate) -> io::Result<()> {
+    let mut terminal = ratatui::try_init().inspect_err(|_| ratatui::restore())?;
+    let result = App::new(dbs, counts, as_of).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct App<'a> {
+    dbs: &'a Databases,
+    counts: &'a UniqueCounts,
+    as_of: NaiveDate,
+    input: String,
+    results: SearchResults<'a>,
+    // Shown in place of results, e.g. a query error
+    message: Option<String>,
+    selected: ListState,
+}
+
+impl<'a> App<'a> {
+    fn new(dbs: &'a Databases, counts: &'a UniqueCounts, as_of: NaiveDate) -> Self {
+        App {
+            dbs,
+            counts,
+            as_of,
+            input: String::new(),
+            results: SearchResults::default(),
+            message: Some("Type a search and press Enter.".to_string()),
+            selected: ListState::default(),
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(())
+                }
+                KeyCode::Enter => self.search(),
+                KeyCode::Up => self.selected.select_previous(),
+                KeyCode::Down => self.selected.select_next(),
+                KeyCode::PageUp => self.selected.scroll_up_by(10),
+                KeyCode::PageDown => self.selected.scroll_down_by(10),
+                KeyCode::Home => self.selected.select_first(),
+                KeyCode::End => self.selected.select_last(),
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    fn search(&mut self) {
+        match search_all(self.dbs, &self.input) {
+            Ok(results) => {
+                self.message = results.is_empty().then(|| {
+                    match fuzzy::did_you_mean(self.dbs, &self.input) {
+                        Some(suggestion) => format!("No matching results found. {}", suggestion),
+                        None => "No matching results found.".to_string(),
+                    }
+                });
+                self.results = results;
+            }
+            Err(err) => {
+                self.message = Some(format!("Invalid search: {}", err));
+                self.results = SearchResults::default();
+            }
+        }
+        self.selected
+            .select((!self.results.assemblies.is_empty()).then_some(0));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [search_area, body_area, footer_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(4),
+        ])
+        .areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(body_area);
+
+        let search = Paragraph::new(self.input.as_str())
+            .block(Block::bordered().title("Search"));
+        frame.render_widget(search, search_area);
+        frame.set_cursor_position((
+            search_area.x + 1 + self.input.chars().count() as u16,
+            search_area.y + 1,
+        ));
+
+        let items: Vec<ListItem> = self
+            .results
+            .assemblies
+            .iter()
+            .map(|found| {
+                ListItem::new(Line::from(vec![
+                    Span::raw(found.assembly.serial_number.clone()),
+                    Span::raw("  "),
+                    Span::styled(
+                        found.assembly.description.clone(),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(
+                "Assemblies ({})",
+                self.results.assemblies.len()
+            )))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.selected);
+
+        let detail = Paragraph::new(self.detail_lines())
+            .block(Block::bordered().title("Details"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(detail, detail_area);
+
+        let footer = Paragraph::new(vec![
+            Line::from(format!(
+                "Unique Drive Manufacturers: {}   Unique Builders: {}   Unique Enclosure S/N's: {}",
+                self.counts.manufacturers, self.counts.builders, self.counts.enclosures
+            )),
+            Line::from(
+                format!(
+                    "Matches: {} assemblies, {} drives, {} Zendesk tickets   Enter search  Up/Down select  Esc quit",
+                    self.results.counts.assemblies,
+                    self.results.counts.drives,
+                    self.results.counts.tickets
+                )
+                .dark_gray(),
+            ),
+        ])
+        .block(Block::bordered());
+        frame.render_widget(footer, footer_area);
+    }
+
+    // The selected assembly with its drives and tickets, or the drives and
+    // tickets that matched on their own when no assembly did.
+    fn detail_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        if let Some(message) = &self.message {
+            lines.push(Line::from(message.clone().yellow()));
+            return lines;
+        }
+
+        if let Some(found) = self
+            .selected
+            .selected()
+            .and_then(|i| self.results.assemblies.get(i))
+        {
+            assembly_lines(found.assembly, &self.dbs.warranty, self.as_of, &mut lines);
+            for drive in &found.drives {
+                lines.push(Line::default());
+                drive_lines(drive, &mut lines);
+            }
+            for ticket in &found.tickets {
+                lines.push(Line::default());
+                ticket_lines(ticket, Some(found.assembly), &self.dbs.warranty, self.as_of, &mut lines);
+            }
+        } else {
+            for found in &self.results.drives {
+                drive_lines(found.drive, &mut lines);
+                if let Some(assembly) = found.enclosure {
+                    lines.push(Line::default());
+                    assembly_lines(assembly, &self.dbs.warranty, self.as_of, &mut lines);
+                }
+                if !found.siblings.is_empty() {
+                    lines.push(Line::default());
+                    lines.push(Line::from("Other drives in this enclosure:".dark_gray()));
+                }
+                for drive in &found.siblings {
+                    drive_lines(drive, &mut lines);
+                }
+                for ticket in &found.tickets {
+                    lines.push(Line::default());
+                    ticket_lines(ticket, found.enclosure, &self.dbs.warranty, self.as_of, &mut lines);
+                }
+                lines.push(Line::default());
+            }
+            for found in &self.results.tickets {
+                ticket_lines(
+                    found.ticket,
+                    found.assembly,
+                    &self.dbs.warranty,
+                    self.as_of,
+                    &mut lines,
+                );
+                if let Some(assembly) = found.assembly {
+                    lines.push(Line::default());
+                    assembly_lines(assembly, &self.dbs.warranty, self.as_of, &mut lines);
+                }
+                if let Some(drive) = found.drive {
+                    lines.push(Line::default());
+                    drive_lines(drive, &mut lines);
+                }
+                if !found.tickets.is_empty() {
+                    lines.push(Line::default());
+                    lines.push(Line::from("Other tickets for this enclosure and its drives:".dark_gray()));
+                }
+                for ticket in &found.tickets {
+                    ticket_lines(ticket, found.assembly, &self.dbs.warranty, self.as_of, &mut lines);
+                }
+                lines.push(Line::default());
+            }
+        }
+        lines
+    }
+}
+
+fn heading(text: &str) -> Line<'static> {
+    Line::from(text.to_string().green().bold())
+}
+
+fn field(label: &str, value: &str) -> Line<'static> {
+    Line::from(vec![
+        Span::raw("    "),
+        Span::raw(format!("{} ", label)).cyan(),
+        Span::raw(value.to_string()),
+    ])
+}
+
+fn push_field(lines: &mut Vec<Line<'static>>, label: &str, value: &str) {
+    if !value.is_empty() {
+        lines.push(field(label, value));
+    }
+}
+
+fn assembly_lines(
+    assembly: &Assembly,
+    rules: &WarrantyRules,
+    as_of: NaiveDate,
+    lines: &mut Vec<Line<'static>>,
+) {
+    lines.push(heading("Assembly:"));
+    push_field(lines, "Serial Number:", &assembly.serial_number);
+    // Zero or out of range dates are treated as missing
+    let built_on = epoch_millis_to_date(assembly.built_date).filter(|_| assembly.built_date != 0);
+    if let Some(built_on) = built_on {
+        push_field(lines, "Built Date:", &built_on.to_string());
+        if let Some(warranty) = rules.warranty(assembly) {
+            lines.push(Line::from(vec![
+                Span::raw("    "),
+                warranty_span(&warranty, as_of),
+            ]));
+        }
+    }
+    push_field(lines, "Built by:", &assembly.built_by);
+    push_field(lines, "Description:", &assembly.description);
+    push_field(lines, "Sales Order:", &assembly.sales_order);
+}
+
+fn drive_lines(drive: &Drive, lines: &mut Vec<Line<'static>>) {
+    lines.push(heading("Drive:"));
+    push_field(lines, "Enclosure SN:", &drive.enclosure_sn);
+    push_field(lines, "Drive SN:", &drive.drive_sn);
+    push_field(lines, "Drive Manufacturer:", &drive.drive_manufacturer);
+    push_field(lines, "Model:", &drive.model);
+    push_field(lines, "Part Number:", &drive.part_number);
+}
+
+// A warranty's status at `date`, green while it lasts and red after.
+fn warranty_span(warranty: &Warranty, date: NaiveDate) -> Span<'static> {
+    let text = warranty.describe(date);
+    if warranty.is_active(date) {
+        text.green().bold()
+    } else {
+        text.red().bold()
+    }
+}
+
+// `assembly` is the assembly shown with the ticket, if any.
+fn ticket_lines(
+    ticket: &ZendeskTicket,
+    assembly: Option<&Assembly>,
+    rules: &WarrantyRules,
+    as_of: NaiveDate,
+    lines: &mut Vec<Line<'static>>,
+) {
+    lines.push(heading("Zendesk Ticket:"));
+    if ticket.rma != 0 {
+        push_field(lines, "RMA:", &ticket.rma.to_string());
+    }
+    if let Some(millis) = ticket.opened_date {
+        // Out of range dates are shown as unknown
+        let opened = epoch_millis_to_date(millis);
+        push_field(
+            lines,
+            "Opened:",
+            &opened.map_or("unknown".to_string(), |date| date.to_string()),
+        );
+        let warranty = assembly.and_then(|a| rules.warranty(a));
+        if let (Some(opened), Some(warranty)) = (opened, warranty) {
+            for (label, date) in [
+                ("When opened:".to_string(), opened),
+                (format!("As of {}:", as_of), as_of),
+            ] {
+                lines.push(Line::from(vec![
+                    Span::raw("    "),
+                    Span::raw(format!("{} ", label)).cyan(),
+                    warranty_span(&warranty, date),
+                ]));
+            }
+        }
+    }
+    push_field(lines, "Serial:", &ticket.serial);
+    push_field(lines, "Drive:", &ticket.drive);
+    push_field(lines, "Assembler:", &ticket.assembler);
+    if let Some(assembly) = assembly.filter(|a| ticket.assembler_mismatch(a)) {
+        lines.push(Line::from(
+            format!("    Assembler differs from Built by: {}", assembly.built_by)
+                .yellow()
+                .bold(),
+        ));
+    }
+    push_field(lines, "Old Diagnosis:", &ticket.old_diagnosis);
+    push_field(lines, "New Diagnosis:", &ticket.new_diagnosis);
+    if let Some(date) = ticket.diagnosis_date {
+        push_field(lines, "Diagnosis Date:", &date.to_string());
+    }
+}
@@ -0,0 +1,342 @@
+use chrono::{Duration, Months, NaiveDate};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::dates::epoch_millis_to_date;
+use crate::loader::{strip_position, LoadError};
+use crate::query::glob_regex;
+use crate::Assembly;
+
+// Name of the term used when no rule applies.
+const DEFAULT_RULE: &str = "Default";
+
+// How long a warranty lasts, e.g. 3y, 18m, 26w or 1095d.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Term {
+    Years(u32),
+    Months(u32),
+    Weeks(u32),
+    Days(u32),
+}
+
+impl Term {
+    // Reads a count and a unit: y, m, w or d, in either case.
+    pub fn parse(text: &str) -> Option<Term> {
+        let unit = text.chars().last()?;
+        let count: u32 = text[..text.len() - unit.len_utf8()].parse().ok()?;
+        match unit.to_ascii_lowercase() {
+            'y' => Some(Term::Years(count)),
+            'm' => Some(Term::Months(count)),
+            'w' => Some(Term::Weeks(count)),
+            'd' => Some(Term::Days(count)),
+            _ => None,
+        }
+    }
+
+    // The last day covered by a warranty starting on `start`.
+    pub fn expiry(self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Term::Years(count) => start.checked_add_months(Months::new(count.checked_mul(12)?)),
+            Term::Months(count) => start.checked_add_months(Months::new(count)),
+            Term::Weeks(count) => start.checked_add_signed(Duration::weeks(count.into())),
+            Term::Days(count) => start.checked_add_signed(Duration::days(count.into())),
+        }
+    }
+
+    // The date this long before `end`.
+    pub fn before(self, end: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Term::Years(count) => end.checked_sub_months(Months::new(count.checked_mul(12)?)),
+            Term::Months(count) => end.checked_sub_months(Months::new(count)),
+            Term::Weeks(count) => end.checked_sub_signed(Duration::weeks(count.into())),
+            Term::Days(count) => end.checked_sub_signed(Duration::days(count.into())),
+        }
+    }
+}
+
+impl TryFrom<String> for Term {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid term `{}`; expected e.g. 3y, 18m, 26w or 1095d",
+                text
+            )
+        };
+        Term::parse(text.trim()).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Years(count) => write!(f, "{}y", count),
+            Term::Months(count) => write!(f, "{}m", count),
+            Term::Weeks(count) => write!(f, "{}w", count),
+            Term::Days(count) => write!(f, "{}d", count),
+        }
+    }
+}
+
+// A Description glob, matched against the whole description ignoring case.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+struct DescriptionPattern(Regex);
+
+impl TryFrom<String> for DescriptionPattern {
+    type Error = String;
+
+    fn try_from(glob: String) -> Result<Self, String> {
+        glob_regex(glob.trim())
+            .map(DescriptionPattern)
+            .map_err(|err| format!("invalid Description pattern `{}`: {}", glob, err))
+    }
+}
+
+// A warranty term for the assemblies matching a Description pattern and/or
+// one of a list of sales orders. A rule with neither applies to every
+// assembly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Description", default)]
+    description: Option<DescriptionPattern>,
+    #[serde(rename = "SalesOrders", default)]
+    sales_orders: Vec<String>,
+    #[serde(rename = "Term")]
+    pub term: Term,
+}
+
+impl Rule {
+    fn applies_to(&self, assembly: &Assembly) -> bool {
+        let description = self
+            .description
+            .as_ref()
+            .is_none_or(|pattern| pattern.0.is_match(assembly.description.trim()));
+        let sales_order = self.sales_orders.is_empty()
+            || self.sales_orders.iter().any(|order| {
+                order
+                    .trim()
+                    .eq_ignore_ascii_case(assembly.sales_order.trim())
+            });
+        description && sales_order
+    }
+}
+
+// Warranty terms by product and sales order, loaded from a rules file:
+//
+//   {
+//     "Default": "3y",
+//     "Rules": [
+//       { "Name": "Extended", "SalesOrders": ["654321"], "Term": "5y" },
+//       { "Name": "Atom 2000", "Description": "Atom 2000*", "Term": "2y" }
+//     ]
+//   }
+//
+// The first rule that applies to an assembly wins; assemblies no rule applies
+// to get the default term.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WarrantyRules {
+    #[serde(rename = "Default", default = "default_term")]
+    default: Term,
+    #[serde(rename = "Rules", default)]
+    rules: Vec<Rule>,
+}
+
+// Three years, counted in days as before rules files existed.
+fn default_term() -> Term {
+    Term::Days(365 * 3)
+}
+
+impl Default for WarrantyRules {
+    fn default() -> Self {
+        WarrantyRules {
+            default: default_term(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+// The warranty of one assembly.
+#[derive(Debug, Clone, Copy)]
+pub struct Warranty<'a> {
+    // Last day covered
+    pub expires: NaiveDate,
+    pub rule: &'a str,
+    pub term: Term,
+}
+
+impl Warranty<'_> {
+    pub fn is_active(&self, today: NaiveDate) -> bool {
+        today <= self.expires
+    }
+
+    // Days until the warranty ends, negative once it has ended.
+    pub fn days_left(&self, today: NaiveDate) -> i64 {
+        self.expires.signed_duration_since(today).num_days()
+    }
+
+    // e.g. "Under warranty until 2020-10-05, 12 days left (rule: Default, 1095d)"
+    // or "Warranty ended on 2020-10-05, 3 days ago (rule: Default, 1095d)"
+    pub fn describe(&self, today: NaiveDate) -> String {
+        let days = self.days_left(today);
+        let status = if self.is_active(today) {
+            format!(
+                "Under warranty until {}, {} left",
+                self.expires,
+                plural_days(days)
+            )
+        } else {
+            format!(
+                "Warranty ended on {}, {} ago",
+                self.expires,
+                plural_days(-days)
+            )
+        };
+        format!("{} (rule: {}, {})", status, self.rule, self.term)
+    }
+}
+
+fn plural_days(count: i64) -> String {
+    if count == 1 {
+        "1 day".to_string()
+    } else {
+        format!("{} days", count)
+    }
+}
+
+impl WarrantyRules {
+    pub fn load(path: &Path) -> Result<Self, LoadError> {
+        let data = std::fs::read_to_string(path).map_err(|source| LoadError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_str(&data).map_err(|err| LoadError::Syntax {
+            path: path.to_path_buf(),
+            line: err.line(),
+            column: err.column(),
+            message: strip_position(&err),
+        })
+    }
+
+    // The name and term of the rule that applies to an assembly.
+    pub fn rule_for(&self, assembly: &Assembly) -> (&str, Term) {
+        match self.rules.iter().find(|rule| rule.applies_to(assembly)) {
+            Some(rule) => (&rule.name, rule.term),
+            None => (DEFAULT_RULE, self.default),
+        }
+    }
+
+    // An assembly's warranty, or None when it has no build date or one out of
+    // range.
+    pub fn warranty(&self, assembly: &Assembly) -> Option<Warranty<'_>> {
+        if assembly.built_date == 0 {
+            return None;
+        }
+        let (rule, term) = self.rule_for(assembly);
+        let expires = term.expiry(epoch_millis_to_date(assembly.built_date)?)?;
+        Some(Warranty {
+            expires,
+            rule,
+            term,
+        })
+    }
+}
+
+// An assembly in the expiry report.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiryEntry<'a> {
+    #[serde(flatten)]
+    pub assembly: &'a Assembly,
+    #[serde(rename = "Expires")]
+    pub expires: String,
+    #[serde(rename = "DaysLeft")]
+    pub days_left: i64,
+    #[serde(rename = "Rule")]
+    pub rule: &'a str,
+    #[serde(skip)]
+    pub warranty: Warranty<'a>,
+}
+
+// The assemblies of one sales order and description in the expiry report,
+// soonest to expire first, and those whose build date is out of range.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiryGroup<'a> {
+    #[serde(rename = "SalesOrder")]
+    pub sales_order: &'a str,
+    #[serde(rename = "Description")]
+    pub description: &'a str,
+    #[serde(rename = "Expiring")]
+    pub expiring: usize,
+    #[serde(rename = "Expired")]
+    pub expired: usize,
+    #[serde(rename = "Assemblies")]
+    pub assemblies: Vec<ExpiryEntry<'a>>,
+    #[serde(rename = "UnknownBuildDate", skip_serializing_if = "Vec::is_empty")]
+    pub unknown: Vec<&'a Assembly>,
+}
+
+// Assemblies whose warranty ends within `within` either side of `as_of`,
+// grouped by sales order and description. Assemblies with a build date that
+// can't be read are listed in their group too, since they may be expiring.
+pub fn expiry_report<'a>(
+    assemblies: &'a [Assembly],
+    rules: &'a WarrantyRules,
+    as_of: NaiveDate,
+    within: Term,
+) -> Vec<ExpiryGroup<'a>> {
+    let from = within.before(as_of).unwrap_or(NaiveDate::MIN);
+    let to = within.expiry(as_of).unwrap_or(NaiveDate::MAX);
+    let mut groups: BTreeMap<(&str, &str), ExpiryGroup> = BTreeMap::new();
+    for assembly in assemblies {
+        let (sales_order, description) = (assembly.sales_order.trim(), assembly.description.trim());
+        let warranty = rules.warranty(assembly);
+        let in_range = warranty.is_some_and(|w| w.expires >= from && w.expires <= to);
+        // Skip warranties outside the window and assemblies without a build
+        // date; a nonzero build date without a warranty is out of range
+        if !in_range && (warranty.is_some() || assembly.built_date == 0) {
+            continue;
+        }
+        let group = groups
+            .entry((sales_order, description))
+            .or_insert_with(|| ExpiryGroup {
+                sales_order,
+                description,
+                expiring: 0,
+                expired: 0,
+                assemblies: Vec::new(),
+                unknown: Vec::new(),
+            });
+        match warranty {
+            Some(warranty) => group.assemblies.push(ExpiryEntry {
+                assembly,
+                expires: warranty.expires.to_string(),
+                days_left: warranty.days_left(as_of),
+                rule: warranty.rule,
+                warranty,
+            }),
+            None => group.unknown.push(assembly),
+        }
+    }
+    groups
+        .into_values()
+        .map(|mut group| {
+            group.assemblies.sort_by_key(|entry| entry.warranty.expires);
+            group.expiring = group
+                .assemblies
+                .iter()
+                .filter(|entry| entry.warranty.is_active(as_of))
+                .count();
+            group.expired = group.assemblies.len() - group.expiring;
+            group
+        })
+        .collect()
+}